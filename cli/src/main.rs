@@ -0,0 +1,815 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::cargo_common_metadata, clippy::multiple_crate_versions)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use obot_converter::{
+    align::AlignOptions,
+    cps::{CPSRule, CPSRuleset},
+    diff::DiffOptions,
+    formats::registry::{FormatRegistry, PhysicsSupport, ReplayFormat},
+    profile::ConversionProfile,
+    resample::{RoundingStrategy, SpeedhackMode},
+    Converter, ConverterError,
+};
+use thiserror::Error;
+
+const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  check-cps found violations, or diff found differences
+  2  invalid arguments, or a format could not be picked
+  3  a file could not be read or written
+  4  the replay could not be loaded or saved";
+
+#[derive(Parser)]
+#[command(
+    name = "obot-convert",
+    version,
+    about = "Convert Geometry Dash macros between bot formats",
+    after_help = EXIT_CODES
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a replay to another format, picked from the output extension
+    Convert {
+        #[command(flatten)]
+        input: InputArgs,
+
+        output: PathBuf,
+
+        /// Output format name, needed when the extension is shared
+        #[arg(long, value_name = "FORMAT")]
+        to: Option<String>,
+
+        /// Don't shift inputs to the frame origin of each format
+        #[arg(long)]
+        no_auto_offset: bool,
+
+        /// Write JSON formats without indentation
+        #[arg(long)]
+        compact_json: bool,
+
+        /// Adjust timing when the output format is for another game version
+        #[arg(long, value_enum, value_name = "PROFILE")]
+        profile: Vec<Profile>,
+    },
+
+    /// Show information about a replay
+    Info {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Remove redundant presses and releases
+    Clean {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Check a replay against the CPS rules of the Impossible Levels List
+    CheckCps(CheckCpsArgs),
+
+    /// Shift every input by a number of frames
+    Offset {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[arg(allow_negative_numbers = true)]
+        frames: i64,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Re-time every input to another TPS (the FPS of 2.1 bots)
+    Resample {
+        #[command(flatten)]
+        input: InputArgs,
+
+        tps: f64,
+
+        /// How inputs that fall between two frames are placed
+        #[arg(long, value_enum, default_value_t = Rounding::Nearest)]
+        rounding: Rounding,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Re-time a replay recorded at one game speed to another
+    Speedhack {
+        #[command(flatten)]
+        input: InputArgs,
+
+        speedhack: f32,
+
+        /// Keep inputs on their frames and change the FPS instead
+        #[arg(long)]
+        keep_frames: bool,
+
+        /// How inputs that fall between two frames are placed
+        #[arg(long, value_enum, default_value_t = Rounding::Nearest)]
+        rounding: Rounding,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Combine P1 of a replay with P2 of another, or append another replay
+    Merge {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Replay to take P2 from, or to append with --at
+        other: PathBuf,
+
+        /// Append the other replay from this frame instead, replacing the
+        /// inputs after it
+        #[arg(long, value_name = "FRAME")]
+        at: Option<u32>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Show the inputs added, removed or moved from one replay to another
+    Diff {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Replay to compare the input to
+        other: PathBuf,
+
+        #[command(flatten)]
+        options: DiffArgs,
+    },
+
+    /// Offset a replay, and resample it if asked to, to line it up with a
+    /// reference
+    Align(AlignArgs),
+
+    /// List the supported formats
+    Formats,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Rounding {
+    Nearest,
+    Floor,
+    Ceil,
+    /// Keep how long each press is held
+    PreserveHoldLength,
+}
+
+impl From<Rounding> for RoundingStrategy {
+    fn from(value: Rounding) -> Self {
+        match value {
+            Rounding::Nearest => Self::Nearest,
+            Rounding::Floor => Self::Floor,
+            Rounding::Ceil => Self::Ceil,
+            Rounding::PreserveHoldLength => Self::PreserveHoldLength,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Profile {
+    /// Resample to 240 TPS
+    #[value(name = "2.1-to-2.2")]
+    V21ToV22,
+    /// Mark the replay as made for 2.1, keeping its frames
+    #[value(name = "2.2-to-2.1")]
+    V22ToV21,
+}
+
+impl From<Profile> for ConversionProfile {
+    fn from(value: Profile) -> Self {
+        match value {
+            Profile::V21ToV22 => Self::v2_1_to_v2_2(),
+            Profile::V22ToV21 => Self::v2_2_to_v2_1(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct CheckCpsArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Check a custom limit on clicks per second instead of the ILL's
+    #[arg(long, value_name = "CLICKS")]
+    max_cps: Option<u32>,
+
+    /// Check a custom limit on clicks per frame instead of the ILL's
+    #[arg(long, value_name = "CLICKS")]
+    max_per_frame: Option<u32>,
+
+    /// Check a custom burst limit instead of the ILL's, like 5:45 for no 5
+    /// clicks in a row at 45 CPS
+    #[arg(long, value_name = "CLICKS:CPS", value_parser = parse_burst)]
+    burst: Option<(u32, f64)>,
+}
+
+impl CheckCpsArgs {
+    /// The custom rules if any were given, or the ILL's.
+    fn ruleset(&self) -> CPSRuleset {
+        let mut ruleset = CPSRuleset::new("custom".to_string());
+        if let Some(clicks) = self.max_cps {
+            ruleset.add_rule(CPSRule::window(clicks, 1.0));
+        }
+        if let Some(clicks) = self.max_per_frame {
+            ruleset.add_rule(CPSRule::per_frame(clicks));
+        }
+        if let Some((clicks, cps)) = self.burst {
+            ruleset.add_rule(CPSRule::burst(clicks, cps));
+        }
+
+        if ruleset.rules.is_empty() {
+            CPSRuleset::ill()
+        } else {
+            ruleset
+        }
+    }
+}
+
+fn parse_burst(value: &str) -> Result<(u32, f64), String> {
+    let (clicks, cps) = value
+        .split_once(':')
+        .ok_or_else(|| "expected CLICKS:CPS".to_string())?;
+    let clicks = clicks.parse().map_err(|e| format!("invalid clicks: {e}"))?;
+    let cps: f64 = cps.parse().map_err(|e| format!("invalid CPS: {e}"))?;
+    if cps <= 0.0 {
+        return Err("CPS must be positive".to_string());
+    }
+
+    Ok((clicks, cps))
+}
+
+#[derive(Args)]
+struct InputArgs {
+    input: PathBuf,
+
+    /// Input format name, detected from the file when not given
+    #[arg(long, value_name = "FORMAT")]
+    from: Option<String>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Where to save the result, overwrites the input when not given
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format name, defaults to the input format
+    #[arg(long, value_name = "FORMAT")]
+    to: Option<String>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// Count inputs moved by at most this many frames as unchanged
+    #[arg(long, default_value_t = 0)]
+    tolerance: u32,
+
+    /// Leave out rows without an input for either player
+    #[arg(long)]
+    ignore_skips: bool,
+}
+
+impl From<DiffArgs> for DiffOptions {
+    fn from(value: DiffArgs) -> Self {
+        Self {
+            ignore_skips: value.ignore_skips,
+            tolerance: value.tolerance,
+        }
+    }
+}
+
+#[derive(Args)]
+struct AlignArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Replay to line the input up with
+    reference: PathBuf,
+
+    /// Largest offset to try, in frames of the reference
+    #[arg(long, default_value_t = 240)]
+    max_offset: u32,
+
+    /// Presses this many frames apart still match
+    #[arg(long, default_value_t = 0)]
+    tolerance: u32,
+
+    /// Estimate the FPS ratio instead of trusting the TPS of both replays
+    #[arg(long)]
+    estimate_ratio: bool,
+
+    /// Only print the estimate
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("Unknown format {0}, see `obot-convert formats`")]
+    UnknownFormat(String),
+
+    #[error("Can't tell the format of {0} from its extension, pass --to")]
+    UnknownExtension(PathBuf),
+
+    #[error("Couldn't detect the format of {0}, pass --from")]
+    Undetected(PathBuf),
+
+    #[error("{0}")]
+    InvalidArgument(ConverterError),
+
+    #[error("{0}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("Failed to load {0}: {1}")]
+    Load(PathBuf, ConverterError),
+
+    #[error("Failed to save {0}: {1}")]
+    Save(PathBuf, ConverterError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::UnknownFormat(_)
+            | Self::UnknownExtension(_)
+            | Self::Undetected(_)
+            | Self::InvalidArgument(_) => ExitCode::from(2),
+            Self::Io(..) => ExitCode::from(3),
+            Self::Load(..) | Self::Save(..) => ExitCode::from(4),
+        }
+    }
+}
+
+/// Finds a format by name, ignoring case.
+fn find_format(registry: &FormatRegistry, name: &str) -> Result<String, CliError> {
+    registry
+        .iter()
+        .find(|format| format.name().eq_ignore_ascii_case(name))
+        .map(|format| format.name().to_string())
+        .ok_or_else(|| CliError::UnknownFormat(name.to_string()))
+}
+
+/// Picks the output format from the file extension. Extensions shared by
+/// several formats (`.replay`, `.echo`, `.slc`) keep the input format when it
+/// is one of them.
+fn format_for_path(
+    registry: &FormatRegistry,
+    path: &Path,
+    input_format: &str,
+) -> Result<String, CliError> {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    // The longest matching extension wins, so `.gdr.json` isn't TASBot
+    let matches_extension = |ext: &str| {
+        filename
+            .strip_suffix(ext)
+            .is_some_and(|name| name.ends_with('.'))
+    };
+    let extension = registry
+        .iter()
+        .flat_map(ReplayFormat::extensions)
+        .filter(|ext| matches_extension(ext))
+        .max_by_key(|ext| ext.len())
+        .ok_or_else(|| CliError::UnknownExtension(path.to_path_buf()))?;
+
+    let formats: Vec<&str> = registry
+        .by_extension(extension)
+        .map(ReplayFormat::name)
+        .collect();
+
+    if formats.contains(&input_format) {
+        return Ok(input_format.to_string());
+    }
+
+    if formats.len() > 1 {
+        eprintln!(
+            "{} could be any of {}, saving as {} (use --to to pick)",
+            path.display(),
+            formats.join(", "),
+            formats[0]
+        );
+    }
+
+    Ok(formats[0].to_string())
+}
+
+/// Loads the input into the converter, returning the name of its format.
+fn load(converter: &mut Converter, input: &InputArgs) -> Result<String, CliError> {
+    let path = &input.input;
+    let data = fs::read(path).map_err(|e| CliError::Io(path.clone(), e))?;
+
+    if let Some(name) = &input.from {
+        let name = find_format(converter.registry(), name)?;
+        converter
+            .load_named(&data, &name)
+            .map_err(|e| CliError::Load(path.clone(), e))?;
+
+        return Ok(name);
+    }
+
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let format = converter.load_auto(&data, filename).map_err(|e| match e {
+        ConverterError::InvalidData => CliError::Undetected(path.clone()),
+        e => CliError::Load(path.clone(), e),
+    })?;
+
+    Ok(format.builtin().name().to_string())
+}
+
+fn save(converter: &mut Converter, path: &Path, format: &str) -> Result<(), CliError> {
+    let data = converter
+        .save_named(format)
+        .map_err(|e| CliError::Save(path.to_path_buf(), e))?;
+
+    fs::write(path, data).map_err(|e| CliError::Io(path.to_path_buf(), e))
+}
+
+/// Saves to `--output` (or over the input) in `--to` (or the input format).
+fn save_output(
+    converter: &mut Converter,
+    input: &InputArgs,
+    output: &OutputArgs,
+    input_format: &str,
+) -> Result<(), CliError> {
+    let path = output.output.as_ref().unwrap_or(&input.input);
+    let format = match &output.to {
+        Some(name) => find_format(converter.registry(), name)?,
+        None => input_format.to_string(),
+    };
+
+    save(converter, path, &format)
+}
+
+fn info(converter: &Converter, format: &str) {
+    let replay = converter.replay();
+
+    let presses = |player_2: bool| {
+        replay
+            .clicks
+            .iter()
+            .filter(|click| {
+                if player_2 {
+                    click.p2.is_click()
+                } else {
+                    click.p1.is_click()
+                }
+            })
+            .count()
+    };
+    let length = replay.clicks.iter().map(|click| click.frame).max();
+
+    println!("Format:       {format}");
+    println!("Game version: {}", replay.game_version);
+    println!("TPS:          {}", replay.tps);
+    if (replay.fps - replay.tps).abs() >= f64::EPSILON {
+        println!("Render FPS:   {}", replay.fps);
+    }
+    if replay.has_speedhack() {
+        println!("Speedhack:    {}x", replay.speedhack);
+    }
+    println!("FPS changes:  {}", replay.fps_changes.len());
+    println!("Inputs:       {}", replay.clicks.len());
+    println!("P1 presses:   {}", presses(false));
+    println!("P2 presses:   {}", presses(true));
+    println!("Left/right:   {}", replay.non_jump_inputs());
+    println!(
+        "Physics:      {}",
+        if replay.has_physics() { "yes" } else { "no" }
+    );
+
+    match length {
+        Some(frames) if replay.fps_changes.is_empty() => println!(
+            "Length:       {frames} frames ({:.2}s)",
+            f64::from(frames) / replay.tps
+        ),
+        Some(frames) => println!("Length:       {frames} frames"),
+        None => println!("Length:       0 frames"),
+    }
+
+    let metadata = &replay.metadata;
+    let fields = [
+        ("Author", metadata.author.clone()),
+        ("Description", metadata.description.clone()),
+        ("Level ID", metadata.level_id.map(|id| id.to_string())),
+        ("Level name", metadata.level_name.clone()),
+        ("Bot", metadata.bot_name.clone()),
+        ("Bot version", metadata.bot_version.clone()),
+        ("Seed", metadata.seed.map(|seed| seed.to_string())),
+        ("Coins", metadata.coins.map(|coins| coins.to_string())),
+        ("LDM", metadata.ldm.map(|ldm| ldm.to_string())),
+        (
+            "Created",
+            metadata.created.map(|created| created.to_string()),
+        ),
+    ];
+
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("{:<14}{value}", format!("{name}:"));
+        }
+    }
+}
+
+fn formats(registry: &FormatRegistry) {
+    for format in registry.iter() {
+        let capabilities = format.capabilities();
+        let mut features = vec![];
+        if capabilities.buttons {
+            features.push("buttons");
+        }
+        if capabilities.fps_changes {
+            features.push("fps changes");
+        }
+        if !capabilities.metadata.is_empty() {
+            features.push("metadata");
+        }
+        match capabilities.physics {
+            PhysicsSupport::Full => features.push("physics"),
+            PhysicsSupport::XPosition => features.push("x position"),
+            PhysicsSupport::PositionAndRotation => features.push("position and rotation"),
+            PhysicsSupport::EchoRecords | PhysicsSupport::FembotRecords => {
+                features.push("own physics records");
+            }
+            PhysicsSupport::None => {}
+        }
+
+        let extensions: Vec<String> = format
+            .extensions()
+            .iter()
+            .map(|ext| format!(".{ext}"))
+            .collect();
+
+        let line = format!(
+            "{:<20}{:<12}{:<8}{}",
+            format.name(),
+            extensions.join(" "),
+            format.game_version().to_string(),
+            features.join(", ")
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Prints every violation of `ruleset`, exiting with 1 if there are any.
+fn check_cps(converter: &Converter, ruleset: &CPSRuleset) -> ExitCode {
+    let violations = converter.cps_violations(ruleset);
+
+    if violations.is_empty() {
+        println!(
+            "No violations found. This macro complies with every {} CPS rule.",
+            ruleset.name
+        );
+        return ExitCode::SUCCESS;
+    }
+
+    for violation in &violations {
+        println!("{violation}");
+    }
+
+    ExitCode::from(1)
+}
+
+/// Loads a second replay, detecting its format.
+fn load_other(path: &Path) -> Result<Converter, CliError> {
+    let input = InputArgs {
+        input: path.to_path_buf(),
+        from: None,
+    };
+    let mut converter = Converter::new();
+    load(&mut converter, &input)?;
+
+    Ok(converter)
+}
+
+/// Prints what changed from the input to `other` as a unified diff.
+fn diff(
+    converter: &mut Converter,
+    input: &InputArgs,
+    other: &Path,
+    options: DiffOptions,
+) -> Result<ExitCode, CliError> {
+    load(converter, input)?;
+    let diff = converter
+        .replay()
+        .diff(load_other(other)?.replay(), options);
+
+    if diff.is_empty() {
+        println!("No differences, {} inputs match", diff.unchanged);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    print!(
+        "{}",
+        diff.unified(
+            &input.input.display().to_string(),
+            &other.display().to_string()
+        )
+    );
+
+    Ok(ExitCode::from(1))
+}
+
+/// Estimates how to line the input up with the reference, and applies it.
+fn align(converter: &mut Converter, args: &AlignArgs) -> Result<(), CliError> {
+    let format = load(converter, &args.input)?;
+    let reference = load_other(&args.reference)?;
+
+    let mut options = AlignOptions::new();
+    options.max_offset = args.max_offset;
+    options.tolerance = args.tolerance;
+    options.estimate_ratio = args.estimate_ratio;
+    let alignment = converter
+        .replay()
+        .estimate_alignment(reference.replay(), options);
+
+    eprintln!(
+        "Offset {} frames, ratio {}: {} of {} presses match ({:.1}%), off by {:.2} frames on average",
+        alignment.offset,
+        alignment.ratio,
+        alignment.matched,
+        alignment.presses,
+        alignment.quality() * 100.0,
+        alignment.mean_error
+    );
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    converter.apply_alignment(&alignment);
+    save_output(converter, &args.input, &args.output, &format)
+}
+
+/// Merges `other` into the input, and saves the result.
+fn merge(
+    converter: &mut Converter,
+    input: &InputArgs,
+    other: &Path,
+    at: Option<u32>,
+    output: &OutputArgs,
+) -> Result<(), CliError> {
+    let format = load(converter, input)?;
+
+    let replay = load_other(other)?.replay().clone();
+
+    let report = match at {
+        Some(frame) => converter.append(replay, frame),
+        None => converter.merge_players_of(replay),
+    };
+    save_output(converter, input, output, &format)?;
+
+    if let Some(tps) = report.resampled_from {
+        eprintln!(
+            "Resampled {} from {tps} to {} TPS",
+            other.display(),
+            converter.get_tps()
+        );
+    }
+    eprintln!("Merged with {} conflicts", report.conflicts.len());
+
+    Ok(())
+}
+
+fn run(command: Command) -> Result<ExitCode, CliError> {
+    let mut converter = Converter::new();
+
+    match command {
+        Command::Convert {
+            input,
+            output,
+            to,
+            no_auto_offset,
+            compact_json,
+            profile,
+        } => {
+            converter.settings.auto_offset = !no_auto_offset;
+            converter.settings.beautified_json = !compact_json;
+            for profile in profile {
+                converter.enable_profile(profile.into());
+            }
+
+            let input_format = load(&mut converter, &input)?;
+            let format = match to {
+                Some(name) => find_format(converter.registry(), &name)?,
+                None => format_for_path(converter.registry(), &output, &input_format)?,
+            };
+
+            save(&mut converter, &output, &format)?;
+            eprintln!("Converted {input_format} to {format}");
+        }
+        Command::Info { input } => {
+            let format = load(&mut converter, &input)?;
+            info(&converter, &format);
+        }
+        Command::Clean { input, output } => {
+            let format = load(&mut converter, &input)?;
+            converter.clean();
+            save_output(&mut converter, &input, &output, &format)?;
+        }
+        Command::CheckCps(args) => {
+            load(&mut converter, &args.input)?;
+            return Ok(check_cps(&converter, &args.ruleset()));
+        }
+        Command::Offset {
+            input,
+            frames,
+            output,
+        } => {
+            let format = load(&mut converter, &input)?;
+            converter.offset_all_by(frames);
+            save_output(&mut converter, &input, &output, &format)?;
+        }
+        Command::Resample {
+            input,
+            tps,
+            rounding,
+            output,
+        } => {
+            let format = load(&mut converter, &input)?;
+            let report = converter
+                .resample(tps, rounding.into())
+                .map_err(CliError::InvalidArgument)?;
+            save_output(&mut converter, &input, &output, &format)?;
+
+            eprintln!(
+                "Resampled to {tps} TPS, {} inputs moved by more than half a frame, {} pushed apart",
+                report.moved, report.collapsed
+            );
+        }
+        Command::Speedhack {
+            input,
+            speedhack,
+            keep_frames,
+            rounding,
+            output,
+        } => {
+            let format = load(&mut converter, &input)?;
+            let mode = if keep_frames {
+                SpeedhackMode::KeepFrames
+            } else {
+                SpeedhackMode::KeepFps
+            };
+            let report = converter
+                .change_speedhack(speedhack, mode, rounding.into())
+                .map_err(CliError::InvalidArgument)?;
+            save_output(&mut converter, &input, &output, &format)?;
+
+            eprintln!(
+                "Changed the speedhack to {speedhack}x, {} inputs moved by more than half a frame, {} pushed apart",
+                report.moved, report.collapsed
+            );
+        }
+        Command::Merge {
+            input,
+            other,
+            at,
+            output,
+        } => merge(&mut converter, &input, &other, at, &output)?,
+        Command::Diff {
+            input,
+            other,
+            options,
+        } => return diff(&mut converter, &input, &other, options.into()),
+        Command::Align(args) => align(&mut converter, &args)?,
+        Command::Formats => formats(converter.registry()),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    run(cli.command).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        e.exit_code()
+    })
+}
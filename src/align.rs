@@ -0,0 +1,377 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::Replay;
+use crate::resample::RoundingStrategy;
+
+/// Frame rates bots commonly record at, used to guess FPS ratios.
+const COMMON_TPS: [f64; 9] = [30.0, 60.0, 120.0, 144.0, 165.0, 240.0, 360.0, 480.0, 1000.0];
+
+/// How two replays are aligned.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlignOptions {
+    /// Largest offset tried, in frames of the reference replay.
+    pub max_offset: u32,
+    /// Presses this many frames apart after aligning still match.
+    pub tolerance: u32,
+    /// Also estimate how many reference frames one frame of the aligned
+    /// replay lasts, instead of trusting their TPS.
+    pub estimate_ratio: bool,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            max_offset: 240,
+            tolerance: 0,
+            estimate_ratio: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl AlignOptions {
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Best found way to line a replay up with a reference: every frame is
+/// multiplied by `ratio`, then moved by `offset`, which puts the replay at
+/// the reference's TPS.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Alignment {
+    pub offset: i32,
+    /// Reference frames per frame of the aligned replay. The TPS ratio of
+    /// the replays unless it was estimated.
+    pub ratio: f64,
+    /// Presses that land on a press of the reference once aligned.
+    pub matched: usize,
+    /// Presses in the replay being aligned.
+    pub presses: usize,
+    /// Presses in the reference.
+    pub reference_presses: usize,
+    /// Average distance between matched presses, in reference frames.
+    pub mean_error: f64,
+    /// TPS of the reference, which the aligned replay ends up at.
+    pub reference_tps: f64,
+}
+
+#[wasm_bindgen]
+impl Alignment {
+    /// Share of presses of both replays that matched, from 0 to 1.
+    #[must_use]
+    pub fn quality(&self) -> f64 {
+        let presses = self.presses.max(self.reference_presses);
+        if presses == 0 {
+            return 0.0;
+        }
+
+        self.matched as f64 / presses as f64
+    }
+}
+
+/// Press frames of each player and button, sorted.
+fn presses(replay: &Replay) -> Vec<Vec<u32>> {
+    let mut presses = vec![vec![]; 6];
+
+    for click in &replay.clicks {
+        let _ = click.apply_button(|frame, hold, player_2, button| {
+            if hold {
+                presses[player_2 as usize * 3 + button as usize - 1].push(frame);
+            }
+            Ok::<_, ()>(())
+        });
+    }
+
+    for stream in &mut presses {
+        stream.sort_unstable();
+    }
+
+    presses
+}
+
+/// Reference frames within `range` frames of `frame`.
+fn near(reference: &[u32], frame: i64, range: u32) -> &[u32] {
+    let start = reference.partition_point(|&other| i64::from(other) < frame - i64::from(range));
+    let end = reference.partition_point(|&other| i64::from(other) <= frame + i64::from(range));
+
+    &reference[start..end]
+}
+
+/// Presses scaled by `ratio` and moved by `offset`, paired with the closest
+/// reference press within the tolerance.
+fn matches(
+    presses: &[Vec<u32>],
+    reference: &[Vec<u32>],
+    ratio: f64,
+    offset: i64,
+    tolerance: u32,
+) -> Vec<(u32, u32)> {
+    let mut matches = vec![];
+
+    for (presses, reference) in presses.iter().zip(reference) {
+        for &frame in presses {
+            let aligned = (f64::from(frame) * ratio).round() as i64 + offset;
+            let closest = near(reference, aligned, tolerance)
+                .iter()
+                .min_by_key(|&&other| (i64::from(other) - aligned).abs());
+
+            if let Some(&other) = closest {
+                matches.push((frame, other));
+            }
+        }
+    }
+
+    matches
+}
+
+/// The offset within `max_offset` that lines up the most presses once
+/// scaled by `ratio`, and how many it lines up. Ties go to the smallest
+/// offset.
+fn best_offset(
+    presses: &[Vec<u32>],
+    reference: &[Vec<u32>],
+    ratio: f64,
+    options: AlignOptions,
+) -> (i64, usize) {
+    let max = i64::from(options.max_offset);
+    let mut histogram = vec![0usize; options.max_offset as usize * 2 + 1];
+
+    for (presses, reference) in presses.iter().zip(reference) {
+        for &frame in presses {
+            let aligned = (f64::from(frame) * ratio).round() as i64;
+            for &other in near(reference, aligned, options.max_offset) {
+                histogram[(i64::from(other) - aligned + max) as usize] += 1;
+            }
+        }
+    }
+
+    // Presses off by up to the tolerance count for an offset too
+    let tolerance = options.tolerance as usize;
+    let score = |index: usize| -> usize {
+        let start = index.saturating_sub(tolerance);
+        let end = (index + tolerance).min(histogram.len() - 1);
+        histogram[start..=end].iter().sum()
+    };
+
+    let best = (0..histogram.len())
+        .max_by_key(|&index| (score(index), std::cmp::Reverse((index as i64 - max).abs())))
+        .unwrap_or(options.max_offset as usize);
+    let offset = best as i64 - max;
+
+    let matched = matches(presses, reference, ratio, offset, options.tolerance).len();
+    (offset, matched)
+}
+
+/// Fits `reference = ratio * frame + offset` through matched presses.
+fn fit(matches: &[(u32, u32)]) -> Option<(f64, f64)> {
+    let n = matches.len() as f64;
+    let mean_x = matches.iter().map(|&(x, _)| f64::from(x)).sum::<f64>() / n;
+    let mean_y = matches.iter().map(|&(_, y)| f64::from(y)).sum::<f64>() / n;
+
+    let mut covariance = 0.0;
+    let mut variance = 0.0;
+    for &(x, y) in matches {
+        covariance += (f64::from(x) - mean_x) * (f64::from(y) - mean_y);
+        variance += (f64::from(x) - mean_x).powi(2);
+    }
+
+    if variance <= 0.0 {
+        return None;
+    }
+
+    let ratio = covariance / variance;
+    Some((ratio, ratio.mul_add(-mean_x, mean_y)))
+}
+
+impl Replay {
+    /// Finds the offset, and ratio if asked to, that lines the presses of
+    /// this replay up with those of `reference` the best.
+    #[must_use]
+    pub fn estimate_alignment(&self, reference: &Self, options: AlignOptions) -> Alignment {
+        let own = presses(self);
+        let reference_presses = presses(reference);
+
+        let tps_ratio = if self.tps > 0.0 && reference.tps > 0.0 {
+            reference.tps / self.tps
+        } else {
+            1.0
+        };
+
+        let mut ratios = vec![tps_ratio];
+        if options.estimate_ratio {
+            ratios.push(1.0);
+            for from in COMMON_TPS {
+                ratios.extend(COMMON_TPS.iter().map(|to| to / from));
+            }
+        }
+
+        let (mut ratio, mut offset, mut matched) = (tps_ratio, 0, 0);
+        for candidate in ratios {
+            let (candidate_offset, candidate_matched) =
+                best_offset(&own, &reference_presses, candidate, options);
+            if candidate_matched > matched {
+                (ratio, offset, matched) = (candidate, candidate_offset, candidate_matched);
+            }
+        }
+
+        // The closest common ratio may still drift over a long replay
+        if options.estimate_ratio {
+            let pairs = matches(&own, &reference_presses, ratio, offset, options.tolerance);
+            if let Some((fitted_ratio, fitted_offset)) = fit(&pairs) {
+                let fitted_offset = fitted_offset.round() as i64;
+                let fitted = matches(
+                    &own,
+                    &reference_presses,
+                    fitted_ratio,
+                    fitted_offset,
+                    options.tolerance,
+                )
+                .len();
+
+                if fitted > matched {
+                    (ratio, offset, matched) = (fitted_ratio, fitted_offset, fitted);
+                }
+            }
+        }
+
+        let pairs = matches(&own, &reference_presses, ratio, offset, options.tolerance);
+        let error: f64 = pairs
+            .iter()
+            .map(|&(frame, other)| {
+                let aligned = (f64::from(frame) * ratio).round() + offset as f64;
+                (f64::from(other) - aligned).abs()
+            })
+            .sum();
+
+        Alignment {
+            offset: offset as i32,
+            ratio,
+            matched,
+            presses: own.iter().map(Vec::len).sum(),
+            reference_presses: reference_presses.iter().map(Vec::len).sum(),
+            mean_error: if pairs.is_empty() {
+                0.0
+            } else {
+                error / pairs.len() as f64
+            },
+            reference_tps: reference.tps,
+        }
+    }
+
+    /// Scales every frame by the alignment's ratio, if it isn't 1, and
+    /// offsets it, then takes on the reference's TPS.
+    pub fn apply_alignment(&mut self, alignment: &Alignment) {
+        if self.tps > 0.0 && (alignment.ratio - 1.0).abs() >= f64::EPSILON {
+            self.resample(self.tps * alignment.ratio, RoundingStrategy::default());
+        }
+        self.shift_frames(alignment.offset.into());
+
+        // An estimated ratio doesn't always match the TPS of both replays
+        if alignment.reference_tps > 0.0 {
+            self.change_tps(alignment.reference_tps);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::Click;
+    use crate::test_utils::{frames, replay};
+
+    const PRESSES: [u32; 8] = [10, 37, 52, 90, 131, 170, 200, 260];
+
+    /// A replay pressing on every frame of `presses` scaled by `scale`, and
+    /// releasing a frame later.
+    fn scaled(tps: f64, scale: u32, offset: u32) -> Replay {
+        let mut replay = replay(tps);
+        for frame in PRESSES {
+            let frame = frame * scale + offset;
+            replay.clicks.push(Click::from_hold(frame, true, false));
+            replay
+                .clicks
+                .push(Click::from_hold(frame + 1, false, false));
+        }
+
+        replay
+    }
+
+    fn press_frames(replay: &Replay) -> Vec<u32> {
+        frames(replay.clicks.iter().filter(|click| click.p1.is_click()))
+    }
+
+    #[test]
+    fn finds_the_offset_between_replays() {
+        let reference = scaled(240.0, 1, 7);
+
+        let alignment = scaled(240.0, 1, 0).estimate_alignment(&reference, AlignOptions::new());
+
+        assert_eq!(alignment.offset, 7);
+        assert!((alignment.ratio - 1.0).abs() < f64::EPSILON);
+        assert_eq!(alignment.matched, PRESSES.len());
+        assert!((alignment.quality() - 1.0).abs() < f64::EPSILON);
+        assert!(alignment.mean_error.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn scales_by_the_tps_ratio() {
+        let reference = scaled(240.0, 4, 3);
+
+        let alignment = scaled(60.0, 1, 0).estimate_alignment(&reference, AlignOptions::new());
+
+        assert_eq!(alignment.offset, 3);
+        assert!((alignment.ratio - 4.0).abs() < f64::EPSILON);
+        assert_eq!(alignment.matched, PRESSES.len());
+    }
+
+    #[test]
+    fn estimates_the_ratio_of_a_wrong_tps() {
+        // Recorded at 60 TPS but saved as 240
+        let mut own = scaled(240.0, 1, 0);
+        let reference = scaled(240.0, 4, 0);
+
+        let alignment = own.estimate_alignment(&reference, AlignOptions::new());
+        assert!(alignment.matched < PRESSES.len());
+
+        let options = AlignOptions {
+            estimate_ratio: true,
+            ..AlignOptions::new()
+        };
+        let alignment = own.estimate_alignment(&reference, options);
+        assert!((alignment.ratio - 4.0).abs() < 1e-9);
+        assert_eq!(alignment.offset, 0);
+        assert_eq!(alignment.matched, PRESSES.len());
+
+        // The frames are scaled, but the TPS is the reference's
+        own.apply_alignment(&alignment);
+        assert_eq!(press_frames(&own), press_frames(&reference));
+        assert!((own.tps - 240.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn applying_puts_the_replay_on_the_reference() {
+        let mut own = scaled(60.0, 1, 0);
+        let reference = scaled(240.0, 4, 3);
+
+        let alignment = own.estimate_alignment(&reference, AlignOptions::new());
+        own.apply_alignment(&alignment);
+
+        assert_eq!(press_frames(&own), press_frames(&reference));
+        assert!((own.tps - 240.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn nothing_to_match_has_no_quality() {
+        let empty = replay(240.0);
+
+        let alignment = empty.estimate_alignment(&scaled(240.0, 1, 0), AlignOptions::new());
+
+        assert_eq!(alignment.matched, 0);
+        assert!(alignment.quality().abs() < f64::EPSILON);
+    }
+}
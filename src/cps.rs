@@ -0,0 +1,318 @@
+use std::fmt::Display;
+
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::Replay;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CPSRuleKind {
+    /// More than `clicks` clicks within `seconds`.
+    Window,
+    /// More than `clicks` clicks on a single frame.
+    PerFrame,
+    /// `clicks` clicks in a row, each less than `seconds` after the last.
+    Burst,
+}
+
+/// A limit on how fast a player may click.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CPSRule {
+    pub kind: CPSRuleKind,
+    pub clicks: u32,
+    pub seconds: f64,
+}
+
+#[wasm_bindgen]
+impl CPSRule {
+    /// At most `clicks` clicks within any `seconds` long window.
+    #[must_use]
+    pub fn window(clicks: u32, seconds: f64) -> Self {
+        Self {
+            kind: CPSRuleKind::Window,
+            clicks,
+            seconds,
+        }
+    }
+
+    /// At most `clicks` clicks on a single frame.
+    #[must_use]
+    pub fn per_frame(clicks: u32) -> Self {
+        Self {
+            kind: CPSRuleKind::PerFrame,
+            clicks,
+            seconds: 0.0,
+        }
+    }
+
+    /// No `clicks` clicks in a row faster than `cps` clicks per second.
+    #[must_use]
+    pub fn burst(clicks: u32, cps: f64) -> Self {
+        Self {
+            kind: CPSRuleKind::Burst,
+            clicks,
+            seconds: 1.0 / cps,
+        }
+    }
+}
+
+impl Display for CPSRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            CPSRuleKind::Window if (self.seconds - 1.0).abs() < f64::EPSILON => {
+                write!(f, "{} CPS", self.clicks)
+            }
+            CPSRuleKind::Window => write!(f, "{} clicks in {}s", self.clicks, self.seconds),
+            CPSRuleKind::PerFrame => write!(f, "{} clicks per frame", self.clicks),
+            CPSRuleKind::Burst => write!(
+                f,
+                "{} CPS in a burst of {} inputs",
+                (1.0 / self.seconds).round(),
+                self.clicks
+            ),
+        }
+    }
+}
+
+/// The CPS rules of a list.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CPSRuleset {
+    pub name: String,
+    pub rules: Vec<CPSRule>,
+}
+
+#[wasm_bindgen]
+impl CPSRuleset {
+    /// An empty ruleset to add custom rules to.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            rules: vec![],
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: CPSRule) {
+        self.rules.push(rule);
+    }
+
+    /// The Impossible Levels List: 15 CPS, 3 clicks per frame, and 45 CPS
+    /// in a burst of 5 inputs.
+    #[must_use]
+    pub fn ill() -> Self {
+        Self {
+            name: "ILL".to_string(),
+            rules: vec![
+                CPSRule::window(15, 1.0),
+                CPSRule::per_frame(3),
+                CPSRule::burst(5, 45.0),
+            ],
+        }
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct CPSViolation {
+    pub rule: CPSRule,
+    pub frame: u32,
+    pub player_2: bool,
+    /// Clicks in the second up to the violation.
+    pub cps: f64,
+}
+
+impl Display for CPSViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let player = if self.player_2 { "P2" } else { "P1" };
+
+        write!(
+            f,
+            "FRAME {} ({player}): {} CPS! Exceeded {}",
+            self.frame, self.cps, self.rule
+        )
+    }
+}
+
+/// Frames of every click of a player, in order.
+fn clicks(replay: &Replay, player_2: bool) -> Vec<u32> {
+    let mut frames: Vec<u32> = replay
+        .clicks
+        .iter()
+        .filter(|click| {
+            if player_2 {
+                click.p2.is_click()
+            } else {
+                click.p1.is_click()
+            }
+        })
+        .map(|click| click.frame)
+        .collect();
+    frames.sort_unstable();
+
+    frames
+}
+
+impl CPSRuleset {
+    /// Every click that breaks a rule, player 1 first.
+    #[must_use]
+    pub fn check(&self, replay: &Replay) -> Vec<CPSViolation> {
+        let mut violations = self.check_player(replay, false);
+        violations.append(&mut self.check_player(replay, true));
+
+        violations
+    }
+
+    fn check_player(&self, replay: &Replay, player_2: bool) -> Vec<CPSViolation> {
+        let frames = clicks(replay, player_2);
+        let mut violations = vec![];
+
+        // First click of the last second and of the current frame
+        let (mut second, mut frame) = (0, 0);
+        // First click of the window, or length of the burst, of each rule
+        let mut state = vec![0; self.rules.len()];
+
+        for (index, &click) in frames.iter().enumerate() {
+            // Moves `start` past the clicks more than `seconds` ago
+            let within = |start: usize, seconds: f64| {
+                let window = seconds * replay.tps;
+                start
+                    + frames[start..=index]
+                        .partition_point(|&other| f64::from(click - other) >= window)
+            };
+
+            second = within(second, 1.0);
+            while frames[frame] != click {
+                frame += 1;
+            }
+            let cps = (index + 1 - second) as f64;
+
+            for (rule, state) in self.rules.iter().zip(&mut state) {
+                let violates = match rule.kind {
+                    CPSRuleKind::Window => {
+                        *state = within(*state, rule.seconds);
+                        index + 1 - *state > rule.clicks as usize
+                    }
+                    CPSRuleKind::PerFrame => index + 1 - frame > rule.clicks as usize,
+                    CPSRuleKind::Burst => {
+                        let max_gap = (rule.seconds * replay.tps).ceil();
+                        if index > 0 && f64::from(click - frames[index - 1]) < max_gap {
+                            *state += 1;
+                        } else {
+                            *state = 1;
+                        }
+                        *state >= rule.clicks as usize
+                    }
+                };
+
+                if violates {
+                    violations.push(CPSViolation {
+                        rule: rule.clone(),
+                        frame: click,
+                        player_2,
+                        cps,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::Click;
+    use crate::test_utils::replay;
+
+    /// A 240 TPS replay with a click of `player_2` on every frame of `frames`.
+    fn clicks(frames: impl IntoIterator<Item = u32>, player_2: bool) -> Replay {
+        let mut replay = replay(240.0);
+        for frame in frames {
+            replay.clicks.push(Click::from_hold(frame, true, player_2));
+            replay.clicks.push(Click::from_hold(frame, false, player_2));
+        }
+
+        replay
+    }
+
+    fn kinds(violations: &[CPSViolation]) -> Vec<(CPSRuleKind, u32)> {
+        violations
+            .iter()
+            .map(|violation| (violation.rule.kind, violation.frame))
+            .collect()
+    }
+
+    #[test]
+    fn clicks_within_a_second_are_limited() {
+        let ill = CPSRuleset::ill();
+
+        assert!(ill
+            .check(&clicks((0..15).map(|i| i * 10), false))
+            .is_empty());
+
+        let violations = ill.check(&clicks((0..16).map(|i| i * 10), false));
+        assert_eq!(kinds(&violations), [(CPSRuleKind::Window, 150)]);
+        assert!((violations[0].cps - 16.0).abs() < f64::EPSILON);
+
+        // The first click is a second old by the 17th
+        assert!(ill
+            .check(&clicks((0..15).map(|i| i * 10).chain([240]), false))
+            .is_empty());
+    }
+
+    #[test]
+    fn clicks_on_one_frame_are_limited() {
+        let ill = CPSRuleset::ill();
+
+        let violations = ill.check(&clicks([100; 4], false));
+
+        assert_eq!(kinds(&violations), [(CPSRuleKind::PerFrame, 100)]);
+    }
+
+    #[test]
+    fn fast_bursts_are_limited() {
+        let ill = CPSRuleset::ill();
+
+        // 48 CPS: 5 frames apart at 240 TPS
+        let violations = ill.check(&clicks((0..5).map(|i| i * 5), false));
+        assert_eq!(kinds(&violations), [(CPSRuleKind::Burst, 20)]);
+
+        // 40 CPS
+        assert!(ill.check(&clicks((0..5).map(|i| i * 6), false)).is_empty());
+    }
+
+    #[test]
+    fn players_are_checked_apart() {
+        let ruleset = CPSRuleset {
+            name: "custom".to_string(),
+            rules: vec![CPSRule::per_frame(1)],
+        };
+        let mut replay = clicks([10, 10], true);
+        replay.clicks.push(Click::from_hold(10, true, false));
+        replay.clicks.push(Click::from_hold(20, true, false));
+        replay.clicks.push(Click::from_hold(20, true, false));
+
+        let violations = ruleset.check(&replay);
+
+        let players: Vec<(bool, u32)> = violations
+            .iter()
+            .map(|violation| (violation.player_2, violation.frame))
+            .collect();
+        assert_eq!(players, [(false, 20), (true, 10)]);
+    }
+
+    #[test]
+    fn rules_describe_their_limit() {
+        assert_eq!(CPSRule::window(15, 1.0).to_string(), "15 CPS");
+        assert_eq!(CPSRule::window(20, 2.0).to_string(), "20 clicks in 2s");
+        assert_eq!(CPSRule::per_frame(3).to_string(), "3 clicks per frame");
+        assert_eq!(
+            CPSRule::burst(5, 45.0).to_string(),
+            "45 CPS in a burst of 5 inputs"
+        );
+    }
+}
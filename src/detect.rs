@@ -0,0 +1,452 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Format;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct FormatCandidate {
+    pub format: Format,
+    /// How sure the detection is, from 0 to 1.
+    pub confidence: f32,
+}
+
+static YBOT_HEADER: [u8; 4] = *b"ybot";
+static REPLAYBOT_HEADER: [u8; 4] = *b"RPLY";
+static FEMBOT_HEADER: [u8; 4] = *b"FBRP";
+static ECHO_BIN_HEADER: [u8; 4] = *b"META";
+static MHR_BINARY_HEADER: [u8; 8] = *b"HACKPRO\x07";
+static SLC2_HEADER: [u8; 4] = *b"SILL";
+static SLC3_HEADER: [u8; 8] = *b"SLC3RPLY";
+static TCM_HEADER: [u8; 4] = [0x9f, 0x88, 0x89, 0x84];
+static CANONICAL_HEADER: [u8; 4] = *b"OBCR";
+
+// Extensions as they appear in file names, longest first so `.gdr.json`
+// wins over `.json`
+static EXTENSIONS: [(&str, &[Format]); 19] = [
+    ("obc.json", &[Format::CanonicalJson]),
+    ("gdr.json", &[Format::GDRJson]),
+    ("mhr.json", &[Format::MHRJson]),
+    (
+        "replay",
+        &[
+            Format::OmegaBot,
+            Format::OmegaBot2,
+            Format::URL,
+            Format::ReplayBot,
+        ],
+    ),
+    ("freplay", &[Format::Fembot]),
+    ("ybot", &[Format::YBot]),
+    (
+        "echo",
+        &[Format::EchoNewBinary, Format::EchoNewJson, Format::EchoOld],
+    ),
+    ("xbot", &[Format::XBot]),
+    ("json", &[Format::Tasbot, Format::GDRJson, Format::MHRJson]),
+    ("mhr", &[Format::MHRBinary]),
+    ("gdr", &[Format::GDR]),
+    ("zbf", &[Format::ZBot]),
+    ("rsh", &[Format::Rush]),
+    ("slc", &[Format::Slc3, Format::Slc2, Format::Silicate]),
+    ("tcm", &[Format::Tcm]),
+    ("txt", &[Format::PlainText]),
+    ("kd", &[Format::KDBot]),
+    ("xd", &[Format::XDBot]),
+    ("obc", &[Format::Canonical]),
+];
+
+/// Confidence added when the file name matches a format's extension.
+const EXTENSION_BONUS: f32 = 0.1;
+/// Confidence for formats only suggested by the file name.
+const EXTENSION_ONLY: f32 = 0.2;
+
+fn f32_at(data: &[u8], at: usize) -> Option<f32> {
+    Some(f32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn f64_at(data: &[u8], at: usize) -> Option<f64> {
+    Some(f64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn is_sane_fps(fps: f64) -> bool {
+    fps.is_finite() && (1.0..=100_000.0).contains(&fps)
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+fn detect_magic(data: &[u8], candidates: &mut Vec<FormatCandidate>) {
+    let len = data.len() as u64;
+    let mut push = |format, confidence| candidates.push(FormatCandidate { format, confidence });
+
+    if data.starts_with(&CANONICAL_HEADER) {
+        push(Format::Canonical, 0.99);
+    } else if data.starts_with(&MHR_BINARY_HEADER) {
+        push(Format::MHRBinary, 0.99);
+    } else if data.starts_with(&SLC3_HEADER) {
+        push(Format::Slc3, 0.99);
+    } else if data.starts_with(&SLC2_HEADER) {
+        push(Format::Slc2, 0.95);
+    } else if data.starts_with(&TCM_HEADER) {
+        push(Format::Tcm, 0.95);
+    } else if data.starts_with(&REPLAYBOT_HEADER) {
+        push(Format::ReplayBot, 0.95);
+    } else if data.starts_with(&FEMBOT_HEADER) {
+        push(Format::Fembot, 0.95);
+    } else if data.starts_with(&ECHO_BIN_HEADER) {
+        push(Format::EchoNewBinary, 0.95);
+    } else if data.starts_with(&YBOT_HEADER) {
+        // yBot 1 shares the magic, but its size follows from the click count
+        let ybot1 = u32_at(data, 8).is_some_and(|count| len == 12 + 8 * count as u64);
+        if ybot1 {
+            push(Format::YBot1, 0.95);
+            push(Format::YBot, 0.3);
+        } else {
+            push(Format::YBot, 0.95);
+            push(Format::YBot1, 0.3);
+        }
+    }
+}
+
+fn detect_json(data: &[u8], candidates: &mut Vec<FormatCandidate>) {
+    let mut push = |format, confidence| candidates.push(FormatCandidate { format, confidence });
+
+    if contains(data, b"\"obot-converter\"") {
+        push(Format::CanonicalJson, 0.99);
+    }
+    if contains(data, b"\"Echo Replay\"") {
+        push(Format::EchoOld, 0.95);
+    }
+    if contains(data, b"\"macro\"") {
+        push(Format::Tasbot, 0.9);
+    }
+    if contains(data, b"\"_\"") && contains(data, b"\"events\"") {
+        push(Format::MHRJson, 0.9);
+    }
+    if contains(data, b"\"inputs\"") {
+        if contains(data, b"\"holding\"") {
+            push(Format::EchoNewJson, 0.85);
+        } else if contains(data, b"\"framerate\"") || contains(data, b"\"gameVersion\"") {
+            push(Format::GDRJson, 0.85);
+        } else {
+            push(Format::GDRJson, 0.5);
+            push(Format::EchoNewJson, 0.4);
+        }
+    }
+}
+
+fn detect_text(text: &str, candidates: &mut Vec<FormatCandidate>) {
+    let mut push = |format, confidence| candidates.push(FormatCandidate { format, confidence });
+
+    let mut lines = text.lines();
+    let Some(first) = lines.next() else {
+        return;
+    };
+    let second = lines.next().unwrap_or_default();
+
+    if first.starts_with("fps: ") {
+        let confidence = if second == "frames" { 0.95 } else { 0.7 };
+        push(Format::XBot, confidence);
+        return;
+    }
+
+    if first.trim().parse::<f32>().is_err() {
+        return;
+    }
+
+    if second.split('|').count() >= 4 {
+        push(Format::XDBot, 0.85);
+    } else if second.split_whitespace().count() == 3 || second.is_empty() {
+        push(Format::PlainText, 0.8);
+    }
+}
+
+fn detect_records(data: &[u8], candidates: &mut Vec<FormatCandidate>) {
+    let mut push = |format, confidence| candidates.push(FormatCandidate { format, confidence });
+    let len = data.len() as u64;
+
+    // zBot: delta, speedhack, then 6 byte clicks using '0'/'1' for booleans
+    if let (Some(delta), Some(speedhack)) = (f32_at(data, 0), f32_at(data, 4)) {
+        let flags_ok = data
+            .get(12..14)
+            .is_none_or(|flags| flags.iter().all(|b| *b == 0x30 || *b == 0x31));
+        if delta > 0.0
+            && delta < 1.0
+            && speedhack > 0.0
+            && speedhack <= 100.0
+            && (len - 8).is_multiple_of(6)
+            && flags_ok
+        {
+            push(Format::ZBot, 0.6);
+        }
+    }
+
+    // Silicate v1: f64 fps, click count, 4 byte clicks
+    if let (Some(fps), Some(count)) = (f64_at(data, 0), u32_at(data, 8)) {
+        if is_sane_fps(fps) && len == 12 + 4 * count as u64 {
+            push(Format::Silicate, 0.8);
+        }
+    }
+
+    // OmegaBot 2 (bincode): initial fps, current fps, replay type, current
+    // click, click count. Clicks take 12 bytes, or 16 for FPS changes
+    if let (Some(fps), Some(replay_type), Some(count)) =
+        (f32_at(data, 0), u32_at(data, 8), u64_at(data, 20))
+    {
+        let clicks_len = len.saturating_sub(28);
+        if is_sane_fps(fps as f64)
+            && replay_type <= 1
+            && count.checked_mul(12).is_some_and(|min| min <= clicks_len)
+            && count.checked_mul(16).is_some_and(|max| clicks_len <= max)
+        {
+            push(Format::OmegaBot2, 0.7);
+        }
+    }
+
+    // OmegaBot 3 (dlhn): initial fps and current fps, followed by variable
+    // length integers, so only the header can be checked
+    if let (Some(fps), Some(current_fps)) = (f32_at(data, 0), f32_at(data, 4)) {
+        if is_sane_fps(fps as f64) && is_sane_fps(current_fps as f64) {
+            push(Format::OmegaBot, 0.4);
+        }
+    }
+
+    if let Some(fps) = f32_at(data, 0) {
+        if is_sane_fps(fps as f64) {
+            // OmegaBot 1: fps, replay type, 5 or 9 byte clicks
+            if let Some(replay_type) = data.get(4) {
+                let click_size = if *replay_type == 2 { 9 } else { 5 };
+                if *replay_type <= 2 && (len - 5).is_multiple_of(click_size) {
+                    push(Format::URL, 0.3);
+                }
+            }
+
+            // KD-Bot: fps, 6 byte clicks
+            if (len - 4).is_multiple_of(6) {
+                push(Format::KDBot, 0.3);
+            }
+        }
+    }
+
+    // Rush: i16 fps, 5 byte clicks
+    if let Some(fps) = data.get(0..2) {
+        let fps = i16::from_le_bytes([fps[0], fps[1]]);
+        if is_sane_fps(fps as f64) && (len - 2).is_multiple_of(5) {
+            push(Format::Rush, 0.3);
+        }
+    }
+}
+
+fn extension_formats(filename: &str) -> &'static [Format] {
+    let filename = filename.to_lowercase();
+
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| {
+            filename
+                .strip_suffix(ext)
+                .is_some_and(|name| name.ends_with('.'))
+        })
+        .map_or(&[], |(_, formats)| formats)
+}
+
+/// Guesses the format of `data`, returning every plausible format with the
+/// most likely one first.
+#[must_use]
+pub fn detect(data: &[u8], filename_hint: Option<&str>) -> Vec<FormatCandidate> {
+    let mut candidates = vec![];
+
+    detect_magic(data, &mut candidates);
+
+    if candidates.is_empty() {
+        let trimmed = data.trim_ascii_start();
+
+        if trimmed.starts_with(b"{") {
+            detect_json(data, &mut candidates);
+        } else if matches!(data.first(), Some(0x80..=0x8f | 0xde | 0xdf))
+            && contains(data, b"inputs")
+        {
+            // GDR is a msgpack map
+            candidates.push(FormatCandidate {
+                format: Format::GDR,
+                confidence: 0.85,
+            });
+        } else if let Ok(text) = std::str::from_utf8(data) {
+            detect_text(text, &mut candidates);
+        }
+
+        if candidates.is_empty() {
+            detect_records(data, &mut candidates);
+        }
+    }
+
+    let extension = filename_hint.map_or(&[][..], extension_formats);
+    for format in extension {
+        match candidates.iter_mut().find(|c| c.format == *format) {
+            Some(candidate) => {
+                candidate.confidence = (candidate.confidence + EXTENSION_BONUS).min(1.0);
+            }
+            None => candidates.push(FormatCandidate {
+                format: *format,
+                confidence: EXTENSION_ONLY,
+            }),
+        }
+    }
+
+    // On a tie, the format the file name points to goes first
+    candidates.sort_by(|a, b| {
+        b.confidence.total_cmp(&a.confidence).then_with(|| {
+            extension
+                .contains(&b.format)
+                .cmp(&extension.contains(&a.format))
+        })
+    });
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formats(data: &[u8], filename: Option<&str>) -> Vec<Format> {
+        detect(data, filename)
+            .into_iter()
+            .map(|candidate| candidate.format)
+            .collect()
+    }
+
+    fn best(data: &[u8], filename: Option<&str>) -> Option<Format> {
+        formats(data, filename).first().copied()
+    }
+
+    /// A 60 FPS header followed by one click, which reads as both
+    /// [`Format::URL`] and [`Format::KDBot`].
+    fn omegabot_or_kdbot() -> Vec<u8> {
+        let mut data = 60f32.to_le_bytes().to_vec();
+        data.extend([0; 6]);
+        data
+    }
+
+    #[test]
+    fn detects_magics() {
+        let magics: [(&[u8], Format); 8] = [
+            (&CANONICAL_HEADER, Format::Canonical),
+            (&MHR_BINARY_HEADER, Format::MHRBinary),
+            (&SLC3_HEADER, Format::Slc3),
+            (&SLC2_HEADER, Format::Slc2),
+            (&TCM_HEADER, Format::Tcm),
+            (&REPLAYBOT_HEADER, Format::ReplayBot),
+            (&FEMBOT_HEADER, Format::Fembot),
+            (&ECHO_BIN_HEADER, Format::EchoNewBinary),
+        ];
+
+        for (magic, format) in magics {
+            let mut data = magic.to_vec();
+            data.extend([0; 16]);
+            assert_eq!(formats(&data, None), [format]);
+        }
+    }
+
+    #[test]
+    fn tells_ybot_versions_apart() {
+        // yBot 1: magic, fps, click count, 8 byte clicks
+        let mut data = YBOT_HEADER.to_vec();
+        data.extend(240f32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        data.extend([0; 16]);
+        assert_eq!(formats(&data, None), [Format::YBot1, Format::YBot]);
+
+        data.push(0);
+        assert_eq!(formats(&data, None), [Format::YBot, Format::YBot1]);
+    }
+
+    #[test]
+    fn tells_silicate_versions_apart() {
+        assert_eq!(best(b"SLC3RPLY\0\0\0\0", None), Some(Format::Slc3));
+        assert_eq!(best(b"SILL\0\0\0\0", None), Some(Format::Slc2));
+
+        // Silicate v1: f64 fps, click count, 4 byte clicks
+        let mut data = 240f64.to_le_bytes().to_vec();
+        data.extend(2u32.to_le_bytes());
+        data.extend([0; 8]);
+        assert_eq!(best(&data, None), Some(Format::Silicate));
+
+        data.push(0);
+        assert!(!formats(&data, None).contains(&Format::Silicate));
+    }
+
+    #[test]
+    fn detects_json() {
+        assert_eq!(
+            best(br#"{"format": "obot-converter"}"#, None),
+            Some(Format::CanonicalJson)
+        );
+        assert_eq!(best(br#"{"macro": []}"#, None), Some(Format::Tasbot));
+        assert_eq!(
+            best(br#"{"framerate": 240, "inputs": []}"#, None),
+            Some(Format::GDRJson)
+        );
+        assert_eq!(
+            best(br#"{"inputs": [{"holding": true}]}"#, None),
+            Some(Format::EchoNewJson)
+        );
+    }
+
+    #[test]
+    fn extension_ranks_ambiguous_data() {
+        let data = omegabot_or_kdbot();
+        assert_eq!(best(&data, Some("macro.kd")), Some(Format::KDBot));
+        assert_eq!(best(&data, Some("macro.replay")), Some(Format::URL));
+
+        let json = br#"{"inputs": []}"#;
+        assert_eq!(best(json, None), Some(Format::GDRJson));
+        assert_eq!(best(json, Some("macro.echo")), Some(Format::EchoNewJson));
+    }
+
+    #[test]
+    fn extension_does_not_override_magic() {
+        assert_eq!(
+            best(&FEMBOT_HEADER, Some("macro.ybot")),
+            Some(Format::Fembot)
+        );
+    }
+
+    #[test]
+    fn longest_extension_wins() {
+        assert_eq!(extension_formats("Macro.GDR.JSON"), [Format::GDRJson]);
+        assert_eq!(
+            extension_formats("macro.json"),
+            [Format::Tasbot, Format::GDRJson, Format::MHRJson]
+        );
+        assert!(extension_formats("gdr").is_empty());
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(detect(&[], None).is_empty());
+        assert_eq!(formats(&[], Some("macro.gdr")), [Format::GDR]);
+        assert!(detect(&[], Some("macro.gdr"))[0].confidence < 0.5);
+    }
+
+    #[test]
+    fn truncated_input() {
+        // A cut off magic is at most a weak guess
+        for len in 1..SLC3_HEADER.len() {
+            let candidates = detect(&SLC3_HEADER[..len], None);
+            assert!(candidates.iter().all(|c| c.confidence < 0.5));
+        }
+
+        // A magic without anything after it is still recognised
+        assert_eq!(best(&YBOT_HEADER, None), Some(Format::YBot));
+    }
+}
@@ -0,0 +1,518 @@
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Button, Replay};
+
+/// Changes less than this many frames apart are shown in the same hunk.
+const HUNK_GAP: u32 = 100;
+
+/// Largest gap between matching inputs that is aligned input by input, in
+/// inputs of the old replay times inputs of the new one. Larger gaps pair
+/// inputs in order instead.
+const MAX_ALIGNED_GAP: usize = 1_000_000;
+
+const BUTTONS: [Button; 3] = [Button::Jump, Button::Left, Button::Right];
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Moved,
+}
+
+/// An input that differs between two replays.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    /// Frame in the old replay, `None` for added inputs.
+    pub old_frame: Option<u32>,
+    /// Frame in the new replay, `None` for removed inputs.
+    pub new_frame: Option<u32>,
+    pub player_2: bool,
+    pub button: Button,
+    pub hold: bool,
+    /// A row without an input for either player. `player_2`, `button` and
+    /// `hold` don't apply to it.
+    pub skip: bool,
+}
+
+#[wasm_bindgen]
+impl DiffEntry {
+    /// Frames the input moved by, `None` unless it moved.
+    #[must_use]
+    pub fn moved_by(&self) -> Option<i32> {
+        Some(self.new_frame? as i32 - self.old_frame? as i32)
+    }
+}
+
+impl DiffEntry {
+    fn describe(&self) -> String {
+        if self.skip {
+            return "skip row".to_string();
+        }
+
+        let player = if self.player_2 { "P2" } else { "P1" };
+        let action = if self.hold { "press" } else { "release" };
+        let button = match self.button {
+            Button::Jump => "",
+            Button::Left => " left",
+            Button::Right => " right",
+        };
+
+        format!("{player} {action}{button}")
+    }
+
+    /// The first frame the entry is on, in either replay.
+    fn frame(&self) -> u32 {
+        self.old_frame.or(self.new_frame).unwrap_or_default()
+    }
+}
+
+/// How two replays are compared.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Leave out rows without an input for either player.
+    pub ignore_skips: bool,
+    /// Inputs moved by at most this many frames count as unchanged.
+    pub tolerance: u32,
+}
+
+#[wasm_bindgen]
+impl DiffOptions {
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Every input added, removed or moved from one replay to another, by frame.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, Default)]
+pub struct ReplayDiff {
+    pub entries: Vec<DiffEntry>,
+    /// Inputs found in both replays, within the tolerance.
+    pub unchanged: usize,
+}
+
+#[wasm_bindgen]
+impl ReplayDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The changes as a unified diff, with hunks by frame. Moved inputs are
+    /// shown as removed from their old frame and added on their new one.
+    #[must_use]
+    pub fn unified(&self, old_name: &str, new_name: &str) -> String {
+        let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+
+        let mut hunks: Vec<&[DiffEntry]> = vec![];
+        let mut start = 0;
+        for (index, pair) in self.entries.windows(2).enumerate() {
+            if pair[1].frame() - pair[0].frame() > HUNK_GAP {
+                hunks.push(&self.entries[start..=index]);
+                start = index + 1;
+            }
+        }
+        if start < self.entries.len() {
+            hunks.push(&self.entries[start..]);
+        }
+
+        for hunk in hunks {
+            let frames = |entry: &DiffEntry| entry.old_frame.into_iter().chain(entry.new_frame);
+            let first = hunk.iter().flat_map(frames).min().unwrap_or_default();
+            let last = hunk.iter().flat_map(frames).max().unwrap_or_default();
+            let _ = writeln!(out, "@@ frames {first}-{last} @@");
+
+            for entry in hunk {
+                let description = entry.describe();
+                if let Some(frame) = entry.old_frame {
+                    let _ = writeln!(out, "-{frame} {description}");
+                }
+                if let Some(frame) = entry.new_frame {
+                    match entry.moved_by() {
+                        Some(moved) => {
+                            let _ = writeln!(out, "+{frame} {description} (moved {moved:+})");
+                        }
+                        None => {
+                            let _ = writeln!(out, "+{frame} {description}");
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// One input of a stream, where every input has the same player and button.
+#[derive(Clone, Copy)]
+struct Input {
+    frame: u32,
+    hold: bool,
+}
+
+/// Inputs of each player and button, followed by skip rows if they are kept,
+/// each sorted by frame.
+fn streams(replay: &Replay, ignore_skips: bool) -> Vec<Vec<Input>> {
+    let mut streams = vec![vec![]; 7];
+
+    for click in &replay.clicks {
+        if click.p1.is_skip() && click.p2.is_skip() {
+            if !ignore_skips {
+                streams[6].push(Input {
+                    frame: click.frame,
+                    hold: false,
+                });
+            }
+            continue;
+        }
+
+        let _ = click.apply_button(|frame, hold, player_2, button| {
+            streams[player_2 as usize * 3 + button as usize - 1].push(Input { frame, hold });
+            Ok::<_, ()>(())
+        });
+    }
+
+    for stream in &mut streams {
+        stream.sort_by_key(|input| input.frame);
+    }
+
+    streams
+}
+
+/// Pairs inputs of the same kind between two streams, keeping their order.
+/// Inputs within `tolerance` frames are paired first, and the ones between
+/// those are paired to need as few unpaired inputs and as little movement
+/// as possible.
+fn pair(old: &[Input], new: &[Input], tolerance: u32) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut anchors = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i].hold == new[j].hold && old[i].frame.abs_diff(new[j].frame) <= tolerance {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if old[i].frame <= new[j].frame {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((old.len(), new.len()));
+
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in anchors {
+        for (a, b) in align(&old[i..anchor_i], &new[j..anchor_j]) {
+            pairs.push((a.map(|a| a + i), b.map(|b| b + j)));
+        }
+        if anchor_i < old.len() {
+            pairs.push((Some(anchor_i), Some(anchor_j)));
+        }
+
+        (i, j) = (anchor_i + 1, anchor_j + 1);
+    }
+
+    pairs
+}
+
+/// Pairs the inputs of a gap between anchors, returning every input of both
+/// exactly once.
+fn align(old: &[Input], new: &[Input]) -> Vec<(Option<usize>, Option<usize>)> {
+    if old.len() * new.len() > MAX_ALIGNED_GAP {
+        return align_in_order(old, new);
+    }
+
+    // Cost of aligning the first i old and j new inputs: unpaired inputs,
+    // then total movement
+    let width = new.len() + 1;
+    let mut cost = vec![(0usize, 0u64); (old.len() + 1) * width];
+    // 0: pair, 1: old unpaired, 2: new unpaired
+    let mut step = vec![0u8; (old.len() + 1) * width];
+
+    for i in 0..=old.len() {
+        for j in 0..=new.len() {
+            if i == 0 && j == 0 {
+                continue;
+            }
+
+            let mut best = if i > 0 && j > 0 && old[i - 1].hold == new[j - 1].hold {
+                let (unpaired, moved) = cost[(i - 1) * width + j - 1];
+                let distance = old[i - 1].frame.abs_diff(new[j - 1].frame);
+                (unpaired, moved + u64::from(distance))
+            } else {
+                (usize::MAX, u64::MAX)
+            };
+            if i > 0 {
+                let (unpaired, moved) = cost[(i - 1) * width + j];
+                if (unpaired + 1, moved) < best {
+                    best = (unpaired + 1, moved);
+                    step[i * width + j] = 1;
+                }
+            }
+            if j > 0 {
+                let (unpaired, moved) = cost[i * width + j - 1];
+                if (unpaired + 1, moved) < best {
+                    best = (unpaired + 1, moved);
+                    step[i * width + j] = 2;
+                }
+            }
+            cost[i * width + j] = best;
+        }
+    }
+
+    let mut pairs = vec![];
+    let (mut i, mut j) = (old.len(), new.len());
+    while i > 0 || j > 0 {
+        match step[i * width + j] {
+            0 => {
+                i -= 1;
+                j -= 1;
+                pairs.push((Some(i), Some(j)));
+            }
+            1 => {
+                i -= 1;
+                pairs.push((Some(i), None));
+            }
+            _ => {
+                j -= 1;
+                pairs.push((None, Some(j)));
+            }
+        }
+    }
+    pairs.reverse();
+
+    pairs
+}
+
+/// Pairs the n-th press and release of one stream with those of the other.
+fn align_in_order(old: &[Input], new: &[Input]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut pairs = vec![];
+
+    for hold in [true, false] {
+        let old = (0..old.len()).filter(|&i| old[i].hold == hold);
+        let mut new = (0..new.len()).filter(|&j| new[j].hold == hold);
+
+        for i in old {
+            pairs.push((Some(i), new.next()));
+        }
+        pairs.extend(new.map(|j| (None, Some(j))));
+    }
+
+    pairs
+}
+
+impl Replay {
+    /// Every input added, removed or moved from this replay to `other`.
+    /// Inputs are matched per player and button, in order.
+    #[must_use]
+    pub fn diff(&self, other: &Self, options: DiffOptions) -> ReplayDiff {
+        let old = streams(self, options.ignore_skips);
+        let new = streams(other, options.ignore_skips);
+        let mut diff = ReplayDiff::default();
+
+        for (stream, (old, new)) in old.iter().zip(&new).enumerate() {
+            let skip = stream == 6;
+            let player_2 = stream >= 3 && !skip;
+            let button = if skip {
+                Button::Jump
+            } else {
+                BUTTONS[stream % 3]
+            };
+
+            for (a, b) in pair(old, new, options.tolerance) {
+                let hold = a
+                    .map(|a| old[a])
+                    .or_else(|| b.map(|b| new[b]))
+                    .is_some_and(|input| input.hold);
+                let old_frame = a.map(|a| old[a].frame);
+                let new_frame = b.map(|b| new[b].frame);
+                let kind = match (old_frame, new_frame) {
+                    (Some(a), Some(b)) if a.abs_diff(b) <= options.tolerance => {
+                        diff.unchanged += 1;
+                        continue;
+                    }
+                    (Some(_), Some(_)) => DiffKind::Moved,
+                    (Some(_), None) => DiffKind::Removed,
+                    _ => DiffKind::Added,
+                };
+
+                diff.entries.push(DiffEntry {
+                    kind,
+                    old_frame,
+                    new_frame,
+                    player_2,
+                    button,
+                    hold,
+                    skip,
+                });
+            }
+        }
+
+        diff.entries.sort_by_key(|entry| {
+            (
+                entry.frame(),
+                entry.skip,
+                entry.player_2,
+                entry.button as u8,
+            )
+        });
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::{Click, ClickType};
+    use crate::test_utils::with_inputs;
+
+    /// Kind, old frame and new frame of every entry.
+    fn entries(diff: &ReplayDiff) -> Vec<(DiffKind, Option<u32>, Option<u32>)> {
+        diff.entries
+            .iter()
+            .map(|entry| (entry.kind, entry.old_frame, entry.new_frame))
+            .collect()
+    }
+
+    #[test]
+    fn same_replays_have_no_differences() {
+        let old = with_inputs(
+            240.0,
+            &[(1, true, false), (5, false, false), (5, true, true)],
+        );
+
+        let diff = old.diff(&old.clone(), DiffOptions::new());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged, 3);
+    }
+
+    #[test]
+    fn moved_inputs_within_the_tolerance_are_unchanged() {
+        let old = with_inputs(240.0, &[(10, true, false), (20, false, false)]);
+        let new = with_inputs(240.0, &[(12, true, false), (20, false, false)]);
+
+        let diff = old.diff(&new, DiffOptions::new());
+        assert_eq!(entries(&diff), [(DiffKind::Moved, Some(10), Some(12))]);
+        assert_eq!(diff.entries[0].moved_by(), Some(2));
+
+        let options = DiffOptions {
+            tolerance: 2,
+            ..DiffOptions::new()
+        };
+        assert!(old.diff(&new, options).is_empty());
+    }
+
+    #[test]
+    fn inserted_inputs_do_not_move_the_rest() {
+        let old = with_inputs(
+            240.0,
+            &[
+                (10, true, false),
+                (20, false, false),
+                (30, true, false),
+                (40, false, false),
+            ],
+        );
+        let new = with_inputs(
+            240.0,
+            &[
+                (10, true, false),
+                (20, false, false),
+                (25, true, false),
+                (27, false, false),
+                (30, true, false),
+                (40, false, false),
+            ],
+        );
+
+        let diff = old.diff(&new, DiffOptions::new());
+
+        assert_eq!(
+            entries(&diff),
+            [
+                (DiffKind::Added, None, Some(25)),
+                (DiffKind::Added, None, Some(27)),
+            ]
+        );
+        assert_eq!(diff.unchanged, 4);
+        assert!(diff.entries[0].hold && !diff.entries[1].hold);
+    }
+
+    #[test]
+    fn inputs_are_matched_per_player() {
+        let old = with_inputs(240.0, &[(10, true, false)]);
+        let new = with_inputs(240.0, &[(10, true, true)]);
+
+        let diff = old.diff(&new, DiffOptions::new());
+
+        assert_eq!(
+            entries(&diff),
+            [
+                (DiffKind::Removed, Some(10), None),
+                (DiffKind::Added, None, Some(10)),
+            ]
+        );
+        assert!(!diff.entries[0].player_2 && diff.entries[1].player_2);
+    }
+
+    #[test]
+    fn skip_rows_can_be_ignored() {
+        let old = with_inputs(240.0, &[(10, true, false)]);
+        let mut new = old.clone();
+        new.clicks.push(Click {
+            p1: ClickType::Skip,
+            ..Click::from_hold(15, false, false)
+        });
+
+        let diff = old.diff(&new, DiffOptions::new());
+        assert_eq!(entries(&diff), [(DiffKind::Added, None, Some(15))]);
+        assert!(diff.entries[0].skip);
+
+        let options = DiffOptions {
+            ignore_skips: true,
+            ..DiffOptions::new()
+        };
+        assert!(old.diff(&new, options).is_empty());
+    }
+
+    #[test]
+    fn large_gaps_are_paired_in_order() {
+        let input = |frame, hold| Input { frame, hold };
+        let old = [input(1, true), input(2, false), input(3, true)];
+        let new = [input(5, true), input(6, false)];
+
+        assert_eq!(
+            align_in_order(&old, &new),
+            [(Some(0), Some(0)), (Some(2), None), (Some(1), Some(1))]
+        );
+    }
+
+    #[test]
+    fn unified_diff_splits_distant_changes_into_hunks() {
+        let old = with_inputs(240.0, &[(10, true, false), (500, true, false)]);
+        let new = with_inputs(
+            240.0,
+            &[(12, true, false), (500, true, false), (700, false, true)],
+        );
+
+        let unified = old.diff(&new, DiffOptions::new()).unified("old", "new");
+
+        assert_eq!(
+            unified,
+            "--- old\n+++ new\n\
+             @@ frames 10-12 @@\n-10 P1 press\n+12 P1 press (moved +2)\n\
+             @@ frames 700-700 @@\n+700 P2 release\n"
+        );
+    }
+}
@@ -0,0 +1,649 @@
+//! The converter's own replay format, which keeps everything a [`Replay`]
+//! knows.
+//!
+//! It has no bot specific quirks (offsets, player encodings, rounded FPS), so
+//! it is meant for archiving macros to re-export them to any bot later.
+//!
+//! Both encodings share the same schema:
+//! - JSON (`.obc.json`): a single object with `"format": "obot-converter"`
+//! - binary (`.obc`): the magic `OBCR`, the schema version as a little endian
+//!   `u32`, then the object as `MessagePack` with named fields
+//!
+//! The object holds `version`, `tps`, `fps` (the TPS when left out),
+//! `speedhack` (1 when left out), `game_version` (`"any"`, `"2.113"` or a 2.2
+//! version from `"2.200"` to `"2.207"`), `metadata`, `fps_changes` (`frame`,
+//! `fps`) and `clicks`
+//! (`frame`, `p1` and `p2` as `"click"`, `"release"` or `"skip"`, `button` as
+//! `"jump"`, `"left"` or `"right"`, and optional `p1_physics`/`p2_physics`
+//! with the Echo or Fembot record they were read from as `echo`/`fembot`, or
+//! the `a` field of an MHR event as `mhr_a`).
+//! Fields without a value are left out, and readers accept replays of their
+//! own schema version or older.
+
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{
+    Button, Click, ClickType, FpsChange, GameVersion, Physics, RawPhysics, Replay, ReplayError,
+    ReplayMetadata,
+};
+
+static CANONICAL_MAGIC: [u8; 4] = *b"OBCR";
+const CANONICAL_FORMAT: &str = "obot-converter";
+
+/// Version of the schema written by this converter. Bump it whenever the
+/// schema changes in a way older readers can't ignore.
+pub const CANONICAL_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum CanonicalGameVersion {
+    #[serde(rename = "any")]
+    Any,
+    #[serde(rename = "2.113")]
+    Version2113,
+    #[serde(rename = "2.200")]
+    Version2200,
+    #[serde(rename = "2.201")]
+    Version2201,
+    #[serde(rename = "2.202")]
+    Version2202,
+    #[serde(rename = "2.203")]
+    Version2203,
+    #[serde(rename = "2.204")]
+    Version2204,
+    #[serde(rename = "2.205")]
+    Version2205,
+    #[serde(rename = "2.206")]
+    Version2206,
+    #[serde(rename = "2.207")]
+    Version2207,
+}
+
+impl From<GameVersion> for CanonicalGameVersion {
+    fn from(value: GameVersion) -> Self {
+        match value {
+            GameVersion::Any => Self::Any,
+            GameVersion::Version2113 => Self::Version2113,
+            GameVersion::Version2200 => Self::Version2200,
+            GameVersion::Version2201 => Self::Version2201,
+            GameVersion::Version2202 => Self::Version2202,
+            GameVersion::Version2203 => Self::Version2203,
+            GameVersion::Version2204 => Self::Version2204,
+            GameVersion::Version2205 => Self::Version2205,
+            GameVersion::Version2206 => Self::Version2206,
+            GameVersion::Version2207 => Self::Version2207,
+        }
+    }
+}
+
+impl From<CanonicalGameVersion> for GameVersion {
+    fn from(value: CanonicalGameVersion) -> Self {
+        match value {
+            CanonicalGameVersion::Any => Self::Any,
+            CanonicalGameVersion::Version2113 => Self::Version2113,
+            CanonicalGameVersion::Version2200 => Self::Version2200,
+            CanonicalGameVersion::Version2201 => Self::Version2201,
+            CanonicalGameVersion::Version2202 => Self::Version2202,
+            CanonicalGameVersion::Version2203 => Self::Version2203,
+            CanonicalGameVersion::Version2204 => Self::Version2204,
+            CanonicalGameVersion::Version2205 => Self::Version2205,
+            CanonicalGameVersion::Version2206 => Self::Version2206,
+            CanonicalGameVersion::Version2207 => Self::Version2207,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum CanonicalClickType {
+    Click,
+    Release,
+    #[default]
+    Skip,
+}
+
+impl CanonicalClickType {
+    // serde passes fields by reference
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn is_skip(&self) -> bool {
+        matches!(self, Self::Skip)
+    }
+}
+
+impl From<ClickType> for CanonicalClickType {
+    fn from(value: ClickType) -> Self {
+        match value {
+            ClickType::Click => Self::Click,
+            ClickType::Release => Self::Release,
+            ClickType::Skip => Self::Skip,
+        }
+    }
+}
+
+impl From<CanonicalClickType> for ClickType {
+    fn from(value: CanonicalClickType) -> Self {
+        match value {
+            CanonicalClickType::Click => Self::Click,
+            CanonicalClickType::Release => Self::Release,
+            CanonicalClickType::Skip => Self::Skip,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum CanonicalButton {
+    #[default]
+    Jump,
+    Left,
+    Right,
+}
+
+impl CanonicalButton {
+    // serde passes fields by reference
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn is_jump(&self) -> bool {
+        matches!(self, Self::Jump)
+    }
+}
+
+impl From<Button> for CanonicalButton {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::Jump => Self::Jump,
+            Button::Left => Self::Left,
+            Button::Right => Self::Right,
+        }
+    }
+}
+
+impl From<CanonicalButton> for Button {
+    fn from(value: CanonicalButton) -> Self {
+        match value {
+            CanonicalButton::Jump => Self::Jump,
+            CanonicalButton::Left => Self::Left,
+            CanonicalButton::Right => Self::Right,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CanonicalPhysics {
+    x: f64,
+    y: f64,
+    rotation: f64,
+    y_velocity: f64,
+    /// Record of an Echo "DBG" action, as read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    echo: Option<Vec<u8>>,
+    /// Record of a Fembot action, as read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fembot: Option<Vec<u8>>,
+    /// The `a` field of an MHR event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mhr_a: Option<f64>,
+}
+
+impl From<Physics> for CanonicalPhysics {
+    fn from(value: Physics) -> Self {
+        let (mut echo, mut fembot, mut mhr_a) = (None, None, None);
+        match value.raw {
+            Some(RawPhysics::Echo(bytes)) => echo = Some(bytes.to_vec()),
+            Some(RawPhysics::Fembot(bytes)) => fembot = Some(bytes.to_vec()),
+            Some(RawPhysics::Mhr { a }) => mhr_a = Some(a),
+            None => {}
+        }
+
+        Self {
+            x: value.x,
+            y: value.y,
+            rotation: value.rotation,
+            y_velocity: value.y_velocity,
+            echo,
+            fembot,
+            mhr_a,
+        }
+    }
+}
+
+impl From<CanonicalPhysics> for Physics {
+    fn from(value: CanonicalPhysics) -> Self {
+        // Records of the wrong size are dropped, the known fields don't need them
+        let echo = value.echo.and_then(|bytes| bytes.try_into().ok());
+        let fembot = value.fembot.and_then(|bytes| bytes.try_into().ok());
+
+        Self {
+            x: value.x,
+            y: value.y,
+            rotation: value.rotation,
+            y_velocity: value.y_velocity,
+            raw: echo
+                .map(RawPhysics::Echo)
+                .or_else(|| fembot.map(RawPhysics::Fembot))
+                .or_else(|| value.mhr_a.map(|a| RawPhysics::Mhr { a })),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanonicalClick {
+    frame: u32,
+    #[serde(default, skip_serializing_if = "CanonicalClickType::is_skip")]
+    p1: CanonicalClickType,
+    #[serde(default, skip_serializing_if = "CanonicalClickType::is_skip")]
+    p2: CanonicalClickType,
+    #[serde(default, skip_serializing_if = "CanonicalButton::is_jump")]
+    button: CanonicalButton,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p1_physics: Option<CanonicalPhysics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p2_physics: Option<CanonicalPhysics>,
+}
+
+impl From<&Click> for CanonicalClick {
+    fn from(value: &Click) -> Self {
+        Self {
+            frame: value.frame,
+            p1: value.p1.into(),
+            p2: value.p2.into(),
+            button: value.button.into(),
+            p1_physics: value.p1_physics.map(Into::into),
+            p2_physics: value.p2_physics.map(Into::into),
+        }
+    }
+}
+
+impl From<CanonicalClick> for Click {
+    fn from(value: CanonicalClick) -> Self {
+        Self {
+            frame: value.frame,
+            p1: value.p1.into(),
+            p2: value.p2.into(),
+            button: value.button.into(),
+            p1_physics: value.p1_physics.map(Into::into),
+            p2_physics: value.p2_physics.map(Into::into),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanonicalFpsChange {
+    frame: u32,
+    fps: f64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CanonicalMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    level_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    level_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bot_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bot_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coins: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ldm: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<i64>,
+}
+
+impl From<&ReplayMetadata> for CanonicalMetadata {
+    fn from(value: &ReplayMetadata) -> Self {
+        Self {
+            author: value.author.clone(),
+            description: value.description.clone(),
+            level_id: value.level_id,
+            level_name: value.level_name.clone(),
+            bot_name: value.bot_name.clone(),
+            bot_version: value.bot_version.clone(),
+            seed: value.seed,
+            coins: value.coins,
+            ldm: value.ldm,
+            created: value.created,
+        }
+    }
+}
+
+impl From<CanonicalMetadata> for ReplayMetadata {
+    fn from(value: CanonicalMetadata) -> Self {
+        Self {
+            author: value.author,
+            description: value.description,
+            level_id: value.level_id,
+            level_name: value.level_name,
+            bot_name: value.bot_name,
+            bot_version: value.bot_version,
+            seed: value.seed,
+            coins: value.coins,
+            ldm: value.ldm,
+            created: value.created,
+        }
+    }
+}
+
+fn default_speedhack() -> f32 {
+    1.0
+}
+
+// serde passes fields by reference
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_speedhack(speedhack: &f32) -> bool {
+    (*speedhack - 1.0).abs() < f32::EPSILON
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanonicalReplay {
+    format: String,
+    version: u32,
+    tps: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fps: Option<f64>,
+    #[serde(
+        default = "default_speedhack",
+        skip_serializing_if = "is_default_speedhack"
+    )]
+    speedhack: f32,
+    game_version: CanonicalGameVersion,
+    #[serde(default)]
+    metadata: CanonicalMetadata,
+    #[serde(default)]
+    fps_changes: Vec<CanonicalFpsChange>,
+    #[serde(default)]
+    clicks: Vec<CanonicalClick>,
+}
+
+fn check_format(format: &str) -> Result<(), ReplayError> {
+    if format != CANONICAL_FORMAT {
+        return Err(ParseDiagnostic::new()
+            .expected(format!("format \"{CANONICAL_FORMAT}\""))
+            .found(format!("\"{format}\""))
+            .into());
+    }
+
+    Ok(())
+}
+
+fn check_version(version: u32) -> Result<(), ReplayError> {
+    if version > CANONICAL_VERSION {
+        return Err(ParseDiagnostic::new()
+            .expected(format!("schema version {CANONICAL_VERSION} or older"))
+            .found(format!("version {version}"))
+            .into());
+    }
+
+    Ok(())
+}
+
+impl Replay {
+    fn load_canonical(&mut self, replay: CanonicalReplay) -> Result<(), ReplayError> {
+        check_format(&replay.format)?;
+        check_version(replay.version)?;
+
+        self.set_tps(replay.tps);
+        if let Some(fps) = replay.fps {
+            self.fps = fps;
+        }
+        self.speedhack = replay.speedhack;
+        self.game_version = replay.game_version.into();
+        self.metadata = replay.metadata.into();
+        self.fps_changes = replay
+            .fps_changes
+            .into_iter()
+            .map(|change| FpsChange {
+                frame: change.frame,
+                fps: change.fps,
+            })
+            .collect();
+        self.clicks = replay.clicks.into_iter().map(Into::into).collect();
+
+        Ok(())
+    }
+
+    fn to_canonical(&self) -> CanonicalReplay {
+        CanonicalReplay {
+            format: CANONICAL_FORMAT.to_owned(),
+            version: CANONICAL_VERSION,
+            tps: self.tps,
+            fps: ((self.fps - self.tps).abs() >= f64::EPSILON).then_some(self.fps),
+            speedhack: self.speedhack,
+            game_version: self.game_version.into(),
+            metadata: (&self.metadata).into(),
+            fps_changes: self
+                .fps_changes
+                .iter()
+                .map(|change| CanonicalFpsChange {
+                    frame: change.frame,
+                    fps: change.fps,
+                })
+                .collect(),
+            clicks: self.clicks.iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn parse_canonical(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
+        let mut reader = BufReader::new(reader);
+
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        if buf != CANONICAL_MAGIC {
+            return Err(ParseDiagnostic::magic(0, &CANONICAL_MAGIC, &buf).into());
+        }
+
+        reader.read_exact(&mut buf)?;
+        check_version(u32::from_le_bytes(buf))?;
+
+        let replay: CanonicalReplay = rmp_serde::from_read(&mut reader)
+            .map_err(|e| ParseDiagnostic::decoder(e, &mut reader))?;
+
+        self.load_canonical(replay)
+    }
+
+    pub fn parse_canonical_json(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
+        let reader = BufReader::new(reader);
+
+        let replay: CanonicalReplay =
+            serde_json::from_reader(reader).map_err(|e| ParseDiagnostic::json(&e))?;
+
+        self.load_canonical(replay)
+    }
+
+    pub fn write_canonical(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
+        let mut writer = BufWriter::new(writer);
+
+        writer.write_all(&CANONICAL_MAGIC)?;
+        writer.write_all(&CANONICAL_VERSION.to_le_bytes())?;
+        rmp_serde::encode::write_named(&mut writer, &self.to_canonical())
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn write_canonical_json(
+        &self,
+        writer: &mut (impl Write + Seek),
+    ) -> Result<(), ReplayError> {
+        let mut writer = BufWriter::new(writer);
+
+        let replay = self.to_canonical();
+        if self.settings.beautified_json {
+            serde_json::to_writer_pretty(&mut writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+        } else {
+            serde_json::to_writer(&mut writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::Settings;
+
+    fn physics(x: f64, raw: Option<RawPhysics>) -> Physics {
+        Physics {
+            x,
+            y: 105.0,
+            rotation: 90.0,
+            y_velocity: -2.5,
+            raw,
+        }
+    }
+
+    /// A replay using every part of the schema.
+    fn full_replay() -> Replay {
+        let mut replay = Replay::new(240.0, GameVersion::Version2206, Settings::default());
+        replay.fps = 60.0;
+        replay.speedhack = 0.5;
+        replay.metadata = ReplayMetadata {
+            author: Some("author".to_owned()),
+            description: Some("description".to_owned()),
+            level_id: Some(128),
+            level_name: Some("Stereo Madness".to_owned()),
+            bot_name: Some("bot".to_owned()),
+            bot_version: Some("1.0".to_owned()),
+            seed: Some(u64::MAX),
+            coins: Some(3),
+            ldm: Some(false),
+            created: Some(1_700_000_000),
+        };
+        replay.fps_changes = vec![FpsChange {
+            frame: 20,
+            fps: 360.0,
+        }];
+
+        let mut echo = [0; 28];
+        echo[27] = 7;
+        let mut fembot = [0; 60];
+        fembot[0] = 9;
+        replay.clicks = vec![
+            Click::from_hold(0, true, false).with_physics(false, Some(physics(1.0, None))),
+            Click::from_button(5, true, true, Button::Left)
+                .with_physics(true, Some(physics(2.0, Some(RawPhysics::Echo(echo))))),
+            Click::from_button(10, false, false, Button::Right)
+                .with_physics(false, Some(physics(3.0, Some(RawPhysics::Fembot(fembot))))),
+            Click::from_hold(15, false, true)
+                .with_physics(true, Some(physics(4.0, Some(RawPhysics::Mhr { a: 1.5 })))),
+            // Skip row
+            Click {
+                frame: 30,
+                p1: ClickType::Skip,
+                p2: ClickType::Skip,
+                button: Button::Jump,
+                p1_physics: None,
+                p2_physics: None,
+            },
+        ];
+
+        replay
+    }
+
+    fn assert_same(loaded: &Replay, replay: &Replay) {
+        assert!((loaded.tps - replay.tps).abs() < f64::EPSILON);
+        assert!((loaded.fps - replay.fps).abs() < f64::EPSILON);
+        assert!((loaded.speedhack - replay.speedhack).abs() < f32::EPSILON);
+        assert_eq!(loaded.game_version, replay.game_version);
+        assert_eq!(loaded.metadata, replay.metadata);
+        assert_eq!(loaded.fps_changes, replay.fps_changes);
+        assert_eq!(loaded.clicks.len(), replay.clicks.len());
+        assert!(loaded.clicks == replay.clicks);
+    }
+
+    fn empty() -> Replay {
+        Replay::new(30.0, GameVersion::Any, Settings::default())
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let replay = full_replay();
+        let mut buffer = Cursor::new(Vec::new());
+        replay.write_canonical(&mut buffer).unwrap();
+        buffer.set_position(0);
+
+        let mut loaded = empty();
+        loaded.parse_canonical(buffer).unwrap();
+        assert_same(&loaded, &replay);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let replay = full_replay();
+        let mut buffer = Cursor::new(Vec::new());
+        replay.write_canonical_json(&mut buffer).unwrap();
+        buffer.set_position(0);
+
+        let mut loaded = empty();
+        loaded.parse_canonical_json(buffer).unwrap();
+        assert_same(&loaded, &replay);
+    }
+
+    #[test]
+    fn leaves_out_defaults() {
+        let mut replay = empty();
+        replay.clicks = vec![Click::from_hold(3, true, false)];
+        let mut buffer = Cursor::new(Vec::new());
+        replay.write_canonical_json(&mut buffer).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(buffer.get_ref()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "format": "obot-converter",
+                "version": 2,
+                "tps": 30.0,
+                "game_version": "any",
+                "metadata": {},
+                "fps_changes": [],
+                "clicks": [{ "frame": 3, "p1": "click" }],
+            })
+        );
+    }
+
+    #[test]
+    fn checks_format() {
+        for json in [
+            r#"{"format":"gdr","version":2,"tps":240.0,"game_version":"any"}"#,
+            r#"{"version":2,"tps":240.0,"game_version":"any"}"#,
+        ] {
+            let mut replay = empty();
+            assert!(replay.parse_canonical_json(Cursor::new(json)).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut replay = empty();
+        let json = r#"{"format":"obot-converter","version":3,"tps":240.0,"game_version":"any"}"#;
+        assert!(replay.parse_canonical_json(Cursor::new(json)).is_err());
+
+        let mut data = CANONICAL_MAGIC.to_vec();
+        data.extend(3u32.to_le_bytes());
+        assert!(replay.parse_canonical(Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn requires_tps() {
+        let mut replay = empty();
+        let json = r#"{"format":"obot-converter","version":2,"fps":240.0,"game_version":"any"}"#;
+        assert!(replay.parse_canonical_json(Cursor::new(json)).is_err());
+    }
+}
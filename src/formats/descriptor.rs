@@ -0,0 +1,76 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::Format;
+
+use super::registry::{Encoding, PhysicsSupport, ReplayFormat, BUILTIN_FORMATS};
+use super::replay::GameVersion;
+
+/// Describes a format for the UI, so names, extensions and features don't
+/// have to be hardcoded there.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FormatDescriptor {
+    pub format: Format,
+    pub name: String,
+    /// File extensions without the leading dot, most common first. Empty
+    /// for formats without one (yBot 1).
+    pub extensions: Vec<String>,
+    pub encoding: Encoding,
+    pub mime_type: String,
+    pub game_version: GameVersion,
+    pub legacy: bool,
+    /// Frame of the converter that the format's frame 0 is on.
+    pub frame_origin: u32,
+    pub two_player: bool,
+    pub buttons: bool,
+    pub fps_changes: bool,
+    /// Whether any metadata (author, level, seed...) is stored.
+    pub metadata: bool,
+    pub physics: PhysicsSupport,
+}
+
+impl FormatDescriptor {
+    /// Describes `implementation`, which handles `format`.
+    #[must_use]
+    pub fn new(format: Format, implementation: &dyn ReplayFormat) -> Self {
+        let capabilities = implementation.capabilities();
+        let encoding = implementation.encoding();
+
+        Self {
+            format,
+            name: implementation.name().to_string(),
+            extensions: implementation
+                .extensions()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            encoding,
+            mime_type: encoding.mime_type().to_string(),
+            game_version: implementation.game_version(),
+            legacy: implementation.legacy(),
+            frame_origin: implementation.frame_origin(),
+            two_player: capabilities.two_player,
+            buttons: capabilities.buttons,
+            fps_changes: capabilities.fps_changes,
+            metadata: !capabilities.metadata.is_empty(),
+            physics: capabilities.physics,
+        }
+    }
+
+    /// Descriptors of every built-in format, in the order of [`Format`].
+    #[must_use]
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN_FORMATS
+            .iter()
+            .map(|builtin| builtin.format.descriptor())
+            .collect()
+    }
+}
+
+impl Format {
+    #[must_use]
+    pub fn descriptor(self) -> FormatDescriptor {
+        FormatDescriptor::new(self, self.builtin())
+    }
+}
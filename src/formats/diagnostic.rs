@@ -0,0 +1,243 @@
+use std::{
+    fmt::Display,
+    io::{Read, Seek, SeekFrom},
+    str::FromStr,
+};
+
+/// Where in the input a parse error happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    /// Byte offset from the start of the file.
+    Offset(u64),
+    /// 1-based line of a text format.
+    Line(usize),
+    /// 1-based line and column of a text format.
+    LineColumn { line: usize, column: usize },
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Offset(offset) => write!(f, "at byte {offset:#x}"),
+            Self::Line(line) => write!(f, "at line {line}"),
+            Self::LineColumn { line, column } => write!(f, "at line {line}, column {column}"),
+        }
+    }
+}
+
+/// What went wrong while parsing a replay, as precisely as the format
+/// allows. Every part is optional, parsers fill in what they know.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    /// Name of the format being parsed, filled in by the converter.
+    pub format: Option<String>,
+    pub location: Option<Location>,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+    /// Message from the underlying decoder (serde, bincode, rmp, dlhn).
+    pub cause: Option<String>,
+}
+
+impl ParseDiagnostic {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn at(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    #[must_use]
+    pub fn at_offset(self, offset: u64) -> Self {
+        self.at(Location::Offset(offset))
+    }
+
+    #[must_use]
+    pub fn at_line(self, line: usize) -> Self {
+        self.at(Location::Line(line))
+    }
+
+    #[must_use]
+    pub fn expected(mut self, expected: impl Display) -> Self {
+        self.expected = Some(expected.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn found(mut self, found: impl Display) -> Self {
+        self.found = Some(found.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn cause(mut self, cause: impl Display) -> Self {
+        self.cause = Some(cause.to_string());
+        self
+    }
+
+    /// A magic number or header that didn't match.
+    #[must_use]
+    pub fn magic(offset: u64, expected: &[u8], found: &[u8]) -> Self {
+        Self::new()
+            .at_offset(offset)
+            .expected(bytes(expected))
+            .found(bytes(found))
+    }
+
+    /// A `serde_json` error, which knows its line and column.
+    #[must_use]
+    pub fn json(error: &serde_json::Error) -> Self {
+        let (line, column) = (error.line(), error.column());
+
+        // The message repeats the location
+        let message = error.to_string();
+        let suffix = format!(" at line {line} column {column}");
+        let message = message.strip_suffix(&suffix).unwrap_or(&message);
+
+        Self::new()
+            .at(Location::LineColumn { line, column })
+            .cause(message)
+    }
+
+    /// A `simd_json` error, which knows its byte offset.
+    #[must_use]
+    pub fn simd_json(error: &simd_json::Error) -> Self {
+        Self::new().at_offset(error.index() as u64).cause(error)
+    }
+
+    /// An error from a binary decoder, located at where `reader` stopped.
+    #[must_use]
+    pub fn decoder(error: impl Display, reader: &mut impl Seek) -> Self {
+        let diagnostic = Self::new().cause(error);
+
+        match reader.stream_position() {
+            Ok(offset) => diagnostic.at_offset(offset),
+            Err(_) => diagnostic,
+        }
+    }
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+
+        if let Some(format) = &self.format {
+            parts.push(format.clone());
+        }
+        if let Some(location) = &self.location {
+            parts.push(location.to_string());
+        }
+        match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => {
+                parts.push(format!("expected {expected}, found {found}"));
+            }
+            (Some(expected), None) => parts.push(format!("expected {expected}")),
+            (None, Some(found)) => parts.push(format!("unexpected {found}")),
+            (None, None) => {}
+        }
+        if let Some(cause) = &self.cause {
+            parts.push(cause.clone());
+        }
+
+        if parts.is_empty() {
+            write!(f, "invalid data")
+        } else {
+            write!(f, "{}", parts.join(": "))
+        }
+    }
+}
+
+/// Takes the next field of a line in a text format.
+pub(crate) fn next_field<'a>(
+    field: Option<&'a str>,
+    line: usize,
+    expected: &str,
+) -> Result<&'a str, ParseDiagnostic> {
+    field.ok_or_else(|| {
+        ParseDiagnostic::new()
+            .at_line(line)
+            .expected(expected)
+            .found("end of line")
+    })
+}
+
+/// Parses the next field of a line in a text format.
+pub(crate) fn parse_field<T>(
+    field: Option<&str>,
+    line: usize,
+    expected: &str,
+) -> Result<T, ParseDiagnostic>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let field = next_field(field, line, expected)?;
+
+    field.parse().map_err(|e| {
+        ParseDiagnostic::new()
+            .at_line(line)
+            .expected(expected)
+            .found(format!("\"{field}\""))
+            .cause(e)
+    })
+}
+
+/// Shows bytes as text when they are printable ASCII, hex otherwise.
+fn bytes(bytes: &[u8]) -> String {
+    if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        format!("\"{}\"", String::from_utf8_lossy(bytes))
+    } else {
+        bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Wraps the input of a parser to remember where it ran out of data, so
+/// truncated files can be reported with an offset.
+pub(crate) struct EofTracker<R> {
+    inner: R,
+    position: u64,
+    eof_at: Option<u64>,
+}
+
+impl<R> EofTracker<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            position: 0,
+            eof_at: None,
+        }
+    }
+
+    /// Offset of the last read that hit the end of the input.
+    pub(crate) fn eof_at(&self) -> Option<u64> {
+        self.eof_at
+    }
+}
+
+impl<R: Read> Read for EofTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+
+        if read == 0 && !buf.is_empty() {
+            self.eof_at = Some(self.position);
+        }
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for EofTracker<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+
+        Ok(self.position)
+    }
+}
@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Click, GameVersion, Physics, RawPhysics, Replay, ReplayError};
 
 #[derive(Serialize, Deserialize)]
 struct EchoOldReplay {
@@ -45,7 +46,13 @@ struct EchoNewClick {
 
 impl From<EchoOldClick> for Click {
     fn from(value: EchoOldClick) -> Self {
-        Self::from_hold(value.frame, value.hold, value.p2)
+        Self::from_hold(value.frame, value.hold, value.p2).with_physics(
+            value.p2,
+            Some(Physics {
+                x: value.xpos as f64,
+                ..Default::default()
+            }),
+        )
     }
 }
 
@@ -58,6 +65,13 @@ impl From<EchoNewClick> for Click {
 static ECHO_BIN_HEADER: [u8; 4] = [0x4D, 0x45, 0x54, 0x41];
 static ECHO_FULL_HEADER: [u8; 4] = [0x44, 0x42, 0x47, 0x00];
 
+const ECHO_HEADER_SIZE: u64 = 48;
+const ECHO_ACTION_SIZE: u64 = 6;
+// Full actions append 28 bytes of physics data, kept whole in `RawPhysics::Echo`
+// for what is read from them
+const ECHO_FULL_ACTION_SIZE: u64 = 34;
+const ECHO_PHYSICS_SIZE: usize = 28;
+
 impl Replay {
     pub fn parse_echo_bin(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         self.game_version = GameVersion::Version2113;
@@ -69,17 +83,21 @@ impl Replay {
 
         reader.read_exact(&mut buf)?;
         if buf != ECHO_BIN_HEADER {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::magic(0, &ECHO_BIN_HEADER, &buf).into());
         }
 
         reader.read_exact(&mut buf)?;
-        let action_size = if buf == ECHO_FULL_HEADER { 34 } else { 6 };
+        let action_size = if buf == ECHO_FULL_HEADER {
+            ECHO_FULL_ACTION_SIZE
+        } else {
+            ECHO_ACTION_SIZE
+        };
 
         reader.seek(std::io::SeekFrom::Start(24))?;
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
 
-        reader.seek(std::io::SeekFrom::Start(48))?;
+        reader.seek(std::io::SeekFrom::Start(ECHO_HEADER_SIZE))?;
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -87,7 +105,7 @@ impl Replay {
             reader.seek(std::io::SeekFrom::Start(old_pos))?;
         }
 
-        let clicks_len = len / action_size;
+        let clicks_len = len.saturating_sub(ECHO_HEADER_SIZE) / action_size;
         self.clicks.reserve(clicks_len as usize);
         for _ in 0..clicks_len {
             reader.read_exact(&mut buf)?;
@@ -98,11 +116,16 @@ impl Replay {
             reader.read_exact(&mut small_buf)?;
             let p2 = small_buf[0] == 1;
 
-            if action_size == 34 {
-                reader.seek(std::io::SeekFrom::Current(28))?;
-            }
+            let physics = if action_size == ECHO_FULL_ACTION_SIZE {
+                let mut physics_buf = [0u8; ECHO_PHYSICS_SIZE];
+                reader.read_exact(&mut physics_buf)?;
+                Some(Physics::from_raw(RawPhysics::Echo(physics_buf)))
+            } else {
+                None
+            };
 
-            self.clicks.push(Click::from_hold(frame, down, p2));
+            self.clicks
+                .push(Click::from_hold(frame, down, p2).with_physics(p2, physics));
         }
 
         Ok(())
@@ -110,9 +133,9 @@ impl Replay {
 
     pub fn parse_echo_new(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         let replay: EchoNewReplay =
-            serde_json::from_reader(reader).map_err(|_| ReplayError::ParseError)?;
+            serde_json::from_reader(reader).map_err(|e| ParseDiagnostic::json(&e))?;
 
-        self.fps = replay.fps.round();
+        self.set_tps(replay.fps.round().into());
         self.clicks = replay.inputs.into_iter().map(EchoNewClick::into).collect();
         self.game_version = GameVersion::Version2113;
 
@@ -121,9 +144,9 @@ impl Replay {
 
     pub fn parse_echo_old(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         let replay: EchoOldReplay =
-            serde_json::from_reader(reader).map_err(|_| ReplayError::ParseError)?;
+            serde_json::from_reader(reader).map_err(|e| ParseDiagnostic::json(&e))?;
 
-        self.fps = replay.fps.round();
+        self.set_tps(replay.fps.round().into());
         self.clicks = replay
             .clicks
             .into_iter()
@@ -141,9 +164,25 @@ impl Replay {
     pub fn write_echo_bin(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
+        // Only write full actions if there are Echo records to put in them,
+        // the fields of other physics can't be placed in one
+        let record = |click: &Click, p2: bool| match click.physics(p2)?.raw? {
+            RawPhysics::Echo(bytes) => Some(bytes),
+            _ => None,
+        };
+        let full = self
+            .clicks
+            .iter()
+            .any(|click| record(click, false).is_some() || record(click, true).is_some());
+
         writer.write_all(&ECHO_BIN_HEADER)?;
-        writer.write_all(&[0u8; 20])?;
-        writer.write_all(&self.fps.to_le_bytes())?;
+        if full {
+            writer.write_all(&ECHO_FULL_HEADER)?;
+            writer.write_all(&[0u8; 16])?;
+        } else {
+            writer.write_all(&[0u8; 20])?;
+        }
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
         writer.write_all(&[0u8; 20])?;
 
         self.clicks.iter().try_for_each(|click| {
@@ -152,16 +191,22 @@ impl Replay {
                 writer.write_all(&(hold as u8).to_le_bytes())?;
                 writer.write_all(&(p2 as u8).to_le_bytes())?;
 
+                if full {
+                    writer.write_all(&record(click, p2).unwrap_or([0u8; ECHO_PHYSICS_SIZE]))?;
+                }
+
                 Ok::<(), ReplayError>(())
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 
     pub fn write_echo_new(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut replay = EchoNewReplay {
-            fps: self.fps,
+            fps: self.tps as f32,
             inputs: vec![],
         };
 
@@ -178,9 +223,11 @@ impl Replay {
         })?;
 
         if self.settings.beautified_json {
-            serde_json::to_writer_pretty(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer_pretty(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         } else {
-            serde_json::to_writer(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         }
 
         Ok(())
@@ -188,7 +235,7 @@ impl Replay {
 
     pub fn write_echo_old(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut replay = EchoOldReplay {
-            fps: self.fps,
+            fps: self.tps as f32,
             start_frame: 0,
             clicks: vec![],
         };
@@ -199,7 +246,10 @@ impl Replay {
                     frame,
                     hold,
                     p2,
-                    xpos: 0.0,
+                    xpos: click
+                        .physics(p2)
+                        .and_then(Physics::verified)
+                        .map_or(0.0, |physics| physics.x as f32),
                 });
 
                 Ok::<(), ReplayError>(())
@@ -207,9 +257,11 @@ impl Replay {
         })?;
 
         if self.settings.beautified_json {
-            serde_json::to_writer_pretty(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer_pretty(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         } else {
-            serde_json::to_writer(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         }
 
         Ok(())
@@ -1,9 +1,16 @@
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Click, GameVersion, Physics, RawPhysics, Replay, ReplayError};
 
 static FEMBOT_HEADER: [u8; 4] = [0x46, 0x42, 0x52, 0x50];
 
+const FEMBOT_HEADER_SIZE: u64 = 8;
+// State and frame, followed by 60 bytes of physics data, kept whole in
+// `RawPhysics::Fembot`
+const FEMBOT_ACTION_SIZE: u64 = 65;
+const FEMBOT_PHYSICS_SIZE: usize = 60;
+
 impl Replay {
     pub fn parse_fembot(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         self.game_version = GameVersion::Version2113;
@@ -15,11 +22,11 @@ impl Replay {
 
         reader.read_exact(&mut buf)?;
         if buf != FEMBOT_HEADER {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::magic(0, &FEMBOT_HEADER, &buf).into());
         }
 
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -27,7 +34,7 @@ impl Replay {
             reader.seek(std::io::SeekFrom::Start(old_pos))?;
         }
 
-        let clicks_len = len / 6;
+        let clicks_len = len.saturating_sub(FEMBOT_HEADER_SIZE) / FEMBOT_ACTION_SIZE;
 
         self.clicks.reserve(clicks_len as usize);
         for _ in 0..clicks_len {
@@ -40,9 +47,15 @@ impl Replay {
             reader.read_exact(&mut buf)?;
             let frame = u32::from_le_bytes(buf);
 
-            reader.seek(std::io::SeekFrom::Current(60))?;
+            let mut physics_buf = [0u8; FEMBOT_PHYSICS_SIZE];
+            reader.read_exact(&mut physics_buf)?;
+
+            // Inputs written without physics data are all zeroes
+            let physics = (physics_buf != [0u8; FEMBOT_PHYSICS_SIZE])
+                .then(|| Physics::from_raw(RawPhysics::Fembot(physics_buf)));
 
-            self.clicks.push(Click::from_hold(frame, hold, p2));
+            self.clicks
+                .push(Click::from_hold(frame, hold, p2).with_physics(p2, physics));
         }
 
         Ok(())
@@ -52,18 +65,26 @@ impl Replay {
         let mut writer = BufWriter::new(writer);
 
         writer.write_all(&FEMBOT_HEADER)?;
-        writer.write_all(&self.fps.to_le_bytes())?;
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
                 writer.write_all(&((hold as u8) | if p2 { 2u8 } else { 0u8 }).to_le_bytes())?;
                 writer.write_all(&frame.to_le_bytes())?;
-                writer.write_all(&[0u8; 60])?;
+                // Only Fembot's own records are written, the fields of other
+                // physics can't be placed in one. Zeroes mean no physics.
+                let record = match click.physics(p2).and_then(|physics| physics.raw) {
+                    Some(RawPhysics::Fembot(bytes)) => bytes,
+                    _ => [0u8; FEMBOT_PHYSICS_SIZE],
+                };
+                writer.write_all(&record)?;
 
                 Ok::<(), ReplayError>(())
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
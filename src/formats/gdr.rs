@@ -1,8 +1,9 @@
-use std::io::{Read, Seek, Write};
+use std::io::{BufReader, Read, Seek, Write};
 
 use serde::{Deserialize, Serialize};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{GameVersion, Replay, ReplayError, ReplayMetadata};
 
 fn default_bot_name() -> String {
     "NATTIE_CONVERTER".to_string()
@@ -14,33 +15,35 @@ fn default_bot_ver() -> String {
 
 #[derive(Serialize, Deserialize, Default)]
 struct BotInfo {
-    #[serde(default = "default_bot_name")]
+    #[serde(default)]
     pub name: String,
-    #[serde(default = "default_bot_ver")]
+    #[serde(default)]
     pub version: String,
 }
 
-fn default_level_name() -> String {
-    "Converted".to_string()
-}
-
-fn default_level_id() -> u32 {
-    12345678
-}
+/// Written in place of a missing author, level ID and level name, and read
+/// back as missing.
+const PLACEHOLDER_AUTHOR: &str = "CONVERTED MACRO";
+const PLACEHOLDER_LEVEL_ID: u32 = 12_345_678;
+const PLACEHOLDER_LEVEL_NAME: &str = "LEVEL NAME";
 
 #[derive(Serialize, Deserialize, Default)]
 struct LevelInfo {
-    #[serde(default = "default_level_id")]
+    #[serde(default)]
     pub id: u32,
-    #[serde(default = "default_level_name")]
+    #[serde(default)]
     pub name: String,
 }
 
+const fn default_button() -> i32 {
+    1
+}
+
 #[derive(Serialize, Deserialize, Default)]
 struct GDRInput {
     #[serde(default)]
     pub frame: u32,
-    #[serde(default, rename = "btn")]
+    #[serde(default = "default_button", rename = "btn")]
     pub button: i32,
     #[serde(default, rename = "2p")]
     pub p2: bool,
@@ -48,13 +51,7 @@ struct GDRInput {
     pub down: bool,
 }
 
-impl From<GDRInput> for Click {
-    fn from(value: GDRInput) -> Self {
-        Self::from_hold(value.frame, value.down, value.p2)
-    }
-}
-
-const fn default_fps() -> f32 {
+const fn default_tps() -> f64 {
     240.0
 }
 
@@ -68,7 +65,7 @@ struct GDRReplay {
     pub version: f32,
     #[serde(default)]
     pub duration: f32,
-    #[serde(default = "default_bot_name")]
+    #[serde(default)]
     pub author: String,
     #[serde(default)]
     pub seed: i32,
@@ -85,40 +82,60 @@ struct GDRReplay {
     #[serde(default, rename = "inputs")]
     pub clicks: Vec<GDRInput>,
 
-    #[serde(default = "default_fps", rename = "framerate")]
-    pub fps: f32,
+    #[serde(default = "default_tps", rename = "framerate")]
+    pub tps: f64,
 }
 
 impl TryFrom<&Replay> for GDRReplay {
     type Error = ReplayError;
 
     fn try_from(orig: &Replay) -> Result<Self, Self::Error> {
-        let dur = (orig.clicks.last().map_or(0, |i| i.frame) as f32) / orig.fps;
+        let dur = (orig.clicks.last().map_or(0, |i| i.frame) as f64 / orig.tps) as f32;
+        let meta = &orig.metadata;
+        // GDR seeds are 32 bits, read as unsigned so they keep their bits
+        let seed = meta
+            .seed
+            .map(u32::try_from)
+            .transpose()
+            .map_err(|_| {
+                ReplayError::WriteError("GDR can't store seeds above 4294967295".to_owned())
+            })?
+            .unwrap_or_default() as i32;
 
         let mut replay = Self {
-            fps: orig.fps,
-            game_version: 2.204,
+            tps: orig.tps,
+            game_version: orig.game_version_2_2().number().unwrap_or_default() as f32,
             version: 1.0,
             duration: dur,
-            author: "CONVERTED MACRO".to_owned(),
+            author: meta
+                .author
+                .clone()
+                .unwrap_or_else(|| PLACEHOLDER_AUTHOR.to_owned()),
+            description: meta.description.clone().unwrap_or_default(),
+            seed,
+            coins: meta.coins.unwrap_or_default(),
+            ldm: meta.ldm.unwrap_or_default(),
             bot_info: BotInfo {
-                name: "NATTIE_CONVERTER".to_string(),
-                version: "1.0.0".to_string(),
+                name: meta.bot_name.clone().unwrap_or_else(default_bot_name),
+                version: meta.bot_version.clone().unwrap_or_else(default_bot_ver),
             },
             level_info: LevelInfo {
-                id: 12345678,
-                name: "LEVEL NAME".to_string(),
+                id: meta.level_id.unwrap_or(PLACEHOLDER_LEVEL_ID),
+                name: meta
+                    .level_name
+                    .clone()
+                    .unwrap_or_else(|| PLACEHOLDER_LEVEL_NAME.to_owned()),
             },
             ..Default::default()
         };
 
         orig.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, down, p2| {
+            click.apply_button(|frame, down, p2, button| {
                 replay.clicks.push(GDRInput {
                     frame,
                     down,
                     p2,
-                    button: 1,
+                    button: button as i32,
                 });
 
                 Ok::<(), ReplayError>(())
@@ -129,25 +146,56 @@ impl TryFrom<&Replay> for GDRReplay {
     }
 }
 
+/// Missing fields and placeholders become `None`. As GDR has no way to tell
+/// them apart, zero seeds and coins and a disabled LDM do too.
+impl From<&GDRReplay> for ReplayMetadata {
+    fn from(value: &GDRReplay) -> Self {
+        let placeholder = |text: &str, placeholder: &str| {
+            Self::non_empty(text.to_owned()).filter(|text| text != placeholder)
+        };
+
+        Self {
+            author: placeholder(&value.author, PLACEHOLDER_AUTHOR),
+            description: Self::non_empty(value.description.clone()),
+            level_id: Some(value.level_info.id).filter(|&id| id != 0 && id != PLACEHOLDER_LEVEL_ID),
+            level_name: placeholder(&value.level_info.name, PLACEHOLDER_LEVEL_NAME),
+            bot_name: Self::non_empty(value.bot_info.name.clone()),
+            bot_version: Self::non_empty(value.bot_info.version.clone()),
+            seed: Some(u64::from(value.seed as u32)).filter(|&seed| seed != 0),
+            coins: Some(value.coins).filter(|&coins| coins != 0),
+            ldm: Some(value.ldm).filter(|&ldm| ldm),
+            created: None,
+        }
+    }
+}
+
 impl Replay {
+    fn load_gdr(&mut self, replay: GDRReplay) {
+        self.set_tps(replay.tps);
+        self.metadata = ReplayMetadata::from(&replay);
+        self.clicks.clear();
+        for input in replay.clicks {
+            self.push_button(input.frame, input.down, input.p2, input.button);
+        }
+        self.game_version = GameVersion::from_number(replay.game_version.into())
+            .unwrap_or(GameVersion::Version2206);
+    }
+
     pub fn parse_gdr(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
-        let replay: GDRReplay =
-            rmp_serde::from_read(reader).map_err(|_| ReplayError::ParseError)?;
+        let mut reader = BufReader::new(reader);
+        let replay: GDRReplay = rmp_serde::from_read(&mut reader)
+            .map_err(|e| ParseDiagnostic::decoder(e, &mut reader))?;
 
-        self.fps = replay.fps.round();
-        self.clicks = replay.clicks.into_iter().map(GDRInput::into).collect();
-        self.game_version = GameVersion::Version2206;
+        self.load_gdr(replay);
 
         Ok(())
     }
 
     pub fn parse_gdr_json(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         let replay: GDRReplay =
-            serde_json::from_reader(reader).map_err(|_| ReplayError::ParseError)?;
+            serde_json::from_reader(reader).map_err(|e| ParseDiagnostic::json(&e))?;
 
-        self.fps = replay.fps.round();
-        self.clicks = replay.clicks.into_iter().map(GDRInput::into).collect();
-        self.game_version = GameVersion::Version2206;
+        self.load_gdr(replay);
 
         Ok(())
     }
@@ -159,7 +207,7 @@ impl Replay {
 
         replay
             .serialize(&mut serializer)
-            .map_err(|_| ReplayError::WriteError)?;
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
 
         Ok(())
     }
@@ -168,11 +216,97 @@ impl Replay {
         let replay = GDRReplay::try_from(self)?;
 
         if self.settings.beautified_json {
-            serde_json::to_writer_pretty(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer_pretty(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         } else {
-            serde_json::to_writer(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer(writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::Settings;
+
+    fn parse_json(json: &str) -> Replay {
+        let mut replay = Replay::new(240.0, GameVersion::Any, Settings::default());
+        replay.parse_gdr_json(Cursor::new(json)).unwrap();
+        replay
+    }
+
+    fn round_trip(metadata: ReplayMetadata) -> ReplayMetadata {
+        let mut replay = Replay::new(240.0, GameVersion::Any, Settings::default());
+        replay.metadata = metadata;
+
+        let mut buffer = Cursor::new(Vec::new());
+        replay.write_gdr(&mut buffer).unwrap();
+        buffer.set_position(0);
+
+        let mut loaded = Replay::new(240.0, GameVersion::Any, Settings::default());
+        loaded.parse_gdr(buffer).unwrap();
+        loaded.metadata
+    }
+
+    #[test]
+    fn missing_metadata_stays_missing() {
+        let replay = parse_json(r#"{"inputs": []}"#);
+
+        assert!(replay.metadata.fields().is_empty());
+    }
+
+    #[test]
+    fn placeholders_read_back_as_missing() {
+        let metadata = round_trip(ReplayMetadata::default());
+
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.level_id, None);
+        assert_eq!(metadata.level_name, None);
+        assert_eq!(metadata.seed, None);
+        assert_eq!(metadata.coins, None);
+        assert_eq!(metadata.ldm, None);
+    }
+
+    #[test]
+    fn keeps_metadata() {
+        let metadata = round_trip(ReplayMetadata {
+            author: Some("author".to_owned()),
+            level_id: Some(128),
+            level_name: Some("Stereo Madness".to_owned()),
+            seed: Some(42),
+            coins: Some(3),
+            ldm: Some(true),
+            ..Default::default()
+        });
+
+        assert_eq!(metadata.author.as_deref(), Some("author"));
+        assert_eq!(metadata.level_id, Some(128));
+        assert_eq!(metadata.level_name.as_deref(), Some("Stereo Madness"));
+        assert_eq!(metadata.seed, Some(42));
+        assert_eq!(metadata.coins, Some(3));
+        assert_eq!(metadata.ldm, Some(true));
+    }
+
+    #[test]
+    fn keeps_seed_bits() {
+        let replay = parse_json(r#"{"seed": -1}"#);
+        assert_eq!(replay.metadata.seed, Some(u64::from(u32::MAX)));
+
+        let metadata = round_trip(replay.metadata);
+        assert_eq!(metadata.seed, Some(u64::from(u32::MAX)));
+    }
+
+    #[test]
+    fn rejects_seeds_above_32_bits() {
+        let mut replay = Replay::new(240.0, GameVersion::Any, Settings::default());
+        replay.metadata.seed = Some(u64::from(u32::MAX) + 1);
+
+        let mut buffer = Cursor::new(Vec::new());
+        assert!(replay.write_gdr(&mut buffer).is_err());
+    }
+}
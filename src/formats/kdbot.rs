@@ -12,7 +12,7 @@ impl Replay {
         let mut buf = [0u8; 4];
 
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -42,7 +42,7 @@ impl Replay {
     pub fn write_kdbot(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        writer.write_all(&self.fps.to_le_bytes())?;
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
@@ -54,6 +54,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -2,7 +2,8 @@ use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
 use serde::{Deserialize, Serialize};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Click, GameVersion, Physics, RawPhysics, Replay, ReplayError};
 
 #[derive(Serialize, Deserialize)]
 struct MHRReplay {
@@ -29,13 +30,29 @@ struct MHRClick {
     pub p2: Option<bool>,
 }
 
+impl MHRClick {
+    fn physics(&self) -> Option<Physics> {
+        if self.x.is_none() && self.y.is_none() && self.r.is_none() && self.a.is_none() {
+            return None;
+        }
+
+        Some(Physics {
+            x: self.x.unwrap_or_default(),
+            y: self.y.unwrap_or_default(),
+            rotation: self.r.unwrap_or_default(),
+            y_velocity: 0.0,
+            // Kept for MHR only, as it isn't known what `a` holds
+            raw: self.a.map(|a| RawPhysics::Mhr { a }),
+        })
+    }
+}
+
 impl From<MHRClick> for Click {
     fn from(value: MHRClick) -> Self {
-        Self::from_hold(
-            value.frame,
-            value.down.unwrap_or(false),
-            value.p2.unwrap_or(false),
-        )
+        let p2 = value.p2.unwrap_or(false);
+
+        Self::from_hold(value.frame, value.down.unwrap_or(false), p2)
+            .with_physics(p2, value.physics())
     }
 }
 
@@ -49,29 +66,35 @@ impl Replay {
         let reader = BufReader::new(reader);
 
         let replay: MHRReplay =
-            serde_json::from_reader(reader).map_err(|_| ReplayError::ParseError)?;
+            serde_json::from_reader(reader).map_err(|e| ParseDiagnostic::json(&e))?;
 
-        self.fps = replay.meta.fps;
+        self.set_tps(replay.meta.fps.into());
         self.clicks = replay.events.into_iter().map(MHRClick::into).collect();
         self.game_version = GameVersion::Version2113;
 
         Ok(())
     }
 
-    pub fn write_mhr_json(&self, writer: impl Write + Seek) -> Result<(), ReplayError> {
+    pub fn write_mhr_json(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
         let mut clicks = Vec::new();
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
+                let raw = click.physics(p2).and_then(|physics| physics.raw);
+                let physics = click.physics(p2).and_then(Physics::verified);
+
                 clicks.push(MHRClick {
                     frame,
                     down: Some(hold),
                     p2: if p2 { Some(p2) } else { None },
-                    a: None,
-                    x: None,
-                    r: None,
-                    y: None,
+                    a: match raw {
+                        Some(RawPhysics::Mhr { a }) => Some(a),
+                        _ => None,
+                    },
+                    x: physics.map(|physics| physics.x),
+                    r: physics.map(|physics| physics.rotation),
+                    y: physics.map(|physics| physics.y),
                 });
 
                 Ok::<(), ReplayError>(())
@@ -81,16 +104,21 @@ impl Replay {
         let replay = MHRReplay {
             tag: "converter by nat :3".to_owned(),
             events: clicks,
-            meta: MHRMeta { fps: self.fps },
+            meta: MHRMeta {
+                fps: self.tps as f32,
+            },
         };
 
         if self.settings.beautified_json {
             serde_json::to_writer_pretty(&mut writer, &replay)
-                .map_err(|_| ReplayError::WriteError)?;
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         } else {
-            serde_json::to_writer(&mut writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer(&mut writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         }
 
+        writer.flush()?;
+
         Ok(())
     }
 }
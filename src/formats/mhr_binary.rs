@@ -1,5 +1,6 @@
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
+use super::diagnostic::ParseDiagnostic;
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 
 static MHR_BINARY_HEADER: [u8; 8] = [0x48, 0x41, 0x43, 0x4B, 0x50, 0x52, 0x4F, 0x07];
@@ -16,7 +17,7 @@ impl Replay {
         let mut header_buf = [0u8; 8];
         reader.read_exact(&mut header_buf)?;
         if header_buf != MHR_BINARY_HEADER {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::magic(0, &MHR_BINARY_HEADER, &header_buf).into());
         }
 
         let mut buf = [0u8; 4];
@@ -25,7 +26,7 @@ impl Replay {
         let meta_size = i32::from_le_bytes(buf);
 
         reader.read_exact(&mut buf)?;
-        self.fps = i32::from_le_bytes(buf) as f32;
+        self.set_tps(i32::from_le_bytes(buf).into());
 
         reader.seek(std::io::SeekFrom::Current((meta_size - 4) as i64))?;
 
@@ -61,12 +62,12 @@ impl Replay {
         Ok(())
     }
 
-    pub fn write_mhr_binary(&self, writer: impl Write + Seek) -> Result<(), ReplayError> {
+    pub fn write_mhr_binary(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
         writer.write_all(&MHR_BINARY_HEADER)?; // Header
         writer.write_all(&4_i32.to_le_bytes())?; // Meta size
-        writer.write_all(&(self.fps as i32).to_le_bytes())?; // FPS
+        writer.write_all(&(self.tps as i32).to_le_bytes())?; // FPS
         writer.write_all(&[0, 0, 0, 0, 0, 0, 0, 0])?; // Reserved
         writer.write_all(&32_u32.to_le_bytes())?; // Event size
                                                   // Event count offset is 0x1c
@@ -96,6 +97,8 @@ impl Replay {
         writer.seek(std::io::SeekFrom::Start(0x1c))?;
         writer.write_all(&event_count.to_le_bytes())?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
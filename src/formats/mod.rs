@@ -1,7 +1,11 @@
 // Trait declaration
+pub mod descriptor;
+pub mod diagnostic;
+pub mod registry;
 pub mod replay;
 
 // Replay formats
+pub mod canonical;
 pub mod echo;
 pub mod fembot;
 pub mod gdr;
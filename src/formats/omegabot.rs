@@ -2,8 +2,9 @@ use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
 use serde::{Deserialize, Serialize};
 
-use crate::formats::replay::{Click, ClickType};
+use crate::formats::replay::{Button, Click, ClickType, FpsChange, ReplayEvent};
 
+use super::diagnostic::ParseDiagnostic;
 use super::replay::{GameVersion, Replay, ReplayError};
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
@@ -59,6 +60,9 @@ impl From<OmegabotClick> for Click {
             } else {
                 ClickType::Skip
             },
+            button: Button::Jump,
+            p1_physics: None,
+            p2_physics: None,
         }
     }
 }
@@ -76,23 +80,45 @@ impl Replay {
         let mut reader = BufReader::new(reader);
 
         let mut deserializer = dlhn::Deserializer::new(&mut reader);
-        let replay =
-            OmegabotReplay::deserialize(&mut deserializer).map_err(|_| ReplayError::ParseError)?;
+        let replay = OmegabotReplay::deserialize(&mut deserializer);
+        let replay = replay.map_err(|e| ParseDiagnostic::decoder(e, &mut reader))?;
 
-        self.fps = replay.initial_fps;
-        self.clicks = replay.clicks.into_iter().map(OmegabotClick::into).collect();
+        self.set_tps(replay.initial_fps.into());
         self.game_version = GameVersion::Version2113;
 
+        for click in replay.clicks {
+            if let OmegabotClickType::FpsChange(fps) = click.click_type {
+                self.fps_changes.push(FpsChange {
+                    frame: click.frame,
+                    fps: fps.into(),
+                });
+            } else {
+                self.clicks.push(click.into());
+            }
+        }
+
         Ok(())
     }
 
-    pub fn write_obot3(&self, writer: impl Write + Seek) -> Result<(), ReplayError> {
-        let writer = BufWriter::new(writer);
+    pub fn write_obot3(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
+        let mut writer = BufWriter::new(writer);
 
-        let mut serializer = dlhn::Serializer::new(writer);
+        let mut serializer = dlhn::Serializer::new(&mut writer);
 
         let mut clicks: Vec<OmegabotClick> = vec![];
-        for click in &self.clicks {
+        for event in self.events() {
+            let click = match event {
+                ReplayEvent::Click(click) => click,
+                ReplayEvent::FpsChange(change) => {
+                    clicks.push(OmegabotClick {
+                        frame: change.frame,
+                        click_type: OmegabotClickType::FpsChange(change.fps as f32),
+                    });
+
+                    continue;
+                }
+            };
+
             click.apply_hold(|frame, hold, p2| {
                 let click_type;
 
@@ -113,15 +139,17 @@ impl Replay {
         }
 
         let replay = OmegabotReplay {
-            initial_fps: self.fps,
-            current_fps: self.fps,
+            initial_fps: self.tps as f32,
+            current_fps: self.tps as f32,
             current: 0,
             clicks,
         };
 
         replay
             .serialize(&mut serializer)
-            .map_err(|_| ReplayError::WriteError)?;
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+
+        writer.flush()?;
 
         Ok(())
     }
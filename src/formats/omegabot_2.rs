@@ -2,7 +2,8 @@ use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
 use serde::{Deserialize, Serialize};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Click, FpsChange, GameVersion, Replay, ReplayError, ReplayEvent};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 enum OmegaBot2Location {
@@ -84,7 +85,9 @@ impl TryFrom<&OmegaBot2Click> for Click {
     fn try_from(value: &OmegaBot2Click) -> Result<Self, Self::Error> {
         let frame = match value.location {
             OmegaBot2Location::Frame(f) => Ok(f),
-            OmegaBot2Location::XPos(_) => Err(ReplayError::ParseError),
+            OmegaBot2Location::XPos(_) => Err(ParseDiagnostic::new()
+                .expected("a frame based replay")
+                .found("an x position based replay")),
         }?;
 
         let hold = value.click_type.is_down();
@@ -96,22 +99,34 @@ impl TryFrom<&OmegaBot2Click> for Click {
 
 impl Replay {
     pub fn parse_obot2(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
-        let reader = BufReader::new(reader);
+        let mut reader = BufReader::new(reader);
 
-        let offset = self.settings.auto_offset as u32;
+        let replay: OmegaBot2Replay = bincode::deserialize_from(&mut reader)
+            .map_err(|e| ParseDiagnostic::decoder(e, &mut reader))?;
 
-        let replay: OmegaBot2Replay =
-            bincode::deserialize_from(reader).map_err(|_| ReplayError::ParseError)?;
-
-        self.fps = replay.initial_fps;
+        self.set_tps(replay.initial_fps.into());
 
         self.clicks.reserve(replay.clicks.len());
         self.game_version = GameVersion::Version2113;
 
         replay.clicks.iter().try_for_each(|click| {
-            let mut c: Click = click.try_into()?;
-            c.frame += offset;
-            self.clicks.push(c);
+            if let OmegaBot2ClickType::FpsChange(fps) = click.click_type {
+                let OmegaBot2Location::Frame(frame) = click.location else {
+                    return Err(ParseDiagnostic::new()
+                        .expected("an FPS change on a frame")
+                        .found("an FPS change on an x position")
+                        .into());
+                };
+
+                self.fps_changes.push(FpsChange {
+                    frame,
+                    fps: fps.into(),
+                });
+
+                return Ok(());
+            }
+
+            self.clicks.push(click.try_into()?);
 
             Ok::<(), ReplayError>(())
         })?;
@@ -122,10 +137,20 @@ impl Replay {
     pub fn write_obot2(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        let offset = self.settings.auto_offset as u32;
-
         let mut clicks = Vec::new();
-        self.clicks.iter().try_for_each(|click| {
+        self.events().into_iter().try_for_each(|event| {
+            let click = match event {
+                ReplayEvent::Click(click) => click,
+                ReplayEvent::FpsChange(change) => {
+                    clicks.push(OmegaBot2Click {
+                        location: OmegaBot2Location::Frame(change.frame),
+                        click_type: OmegaBot2ClickType::FpsChange(change.fps as f32),
+                    });
+
+                    return Ok(());
+                }
+            };
+
             click.apply_hold(|frame, hold, p2| {
                 let click_type;
 
@@ -140,7 +165,7 @@ impl Replay {
                 }
 
                 clicks.push(OmegaBot2Click {
-                    location: OmegaBot2Location::Frame(frame - offset),
+                    location: OmegaBot2Location::Frame(frame),
                     click_type,
                 });
 
@@ -150,13 +175,16 @@ impl Replay {
 
         let replay = OmegaBot2Replay {
             clicks,
-            initial_fps: self.fps,
-            current_fps: self.fps,
+            initial_fps: self.tps as f32,
+            current_fps: self.tps as f32,
             current_click: 0,
             replay_type: OmegaBot2ReplayType::Frame,
         };
 
-        bincode::serialize_into(&mut writer, &replay).map_err(|_| ReplayError::WriteError)?;
+        bincode::serialize_into(&mut writer, &replay)
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+
+        writer.flush()?;
 
         Ok(())
     }
@@ -1,3 +1,4 @@
+use super::diagnostic::{next_field, parse_field, ParseDiagnostic};
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 use std::io::{BufRead, Read, Seek, Write};
 
@@ -7,26 +8,19 @@ impl Replay {
         let mut lines = reader.lines();
 
         self.game_version = GameVersion::Any;
-        self.fps = lines
-            .next()
-            .ok_or(ReplayError::ParseError)?
-            .map_err(|_| ReplayError::ParseError)?
-            .parse::<f32>()
-            .map_err(|_| ReplayError::ParseError)?;
-
-        lines.try_for_each(|line| {
-            let line = line.map_err(|_| ReplayError::ParseError)?;
+        let fps = lines.next().transpose()?;
+        self.set_tps(parse_field(fps.as_deref().map(str::trim), 1, "FPS")?);
+
+        lines.enumerate().try_for_each(|(idx, line)| {
+            let line_number = idx + 2;
+            let line = line.map_err(|e| ParseDiagnostic::new().at_line(line_number).cause(e))?;
             let mut data = line.split_whitespace();
 
-            let frame = data
-                .next()
-                .ok_or(ReplayError::ParseError)?
-                .parse::<u32>()
-                .map_err(|_| ReplayError::ParseError)?;
+            let frame = parse_field::<u32>(data.next(), line_number, "frame")?;
 
-            let hold = data.next().ok_or(ReplayError::ParseError)? == "1";
+            let hold = next_field(data.next(), line_number, "hold")? == "1";
 
-            let player_2 = data.next().ok_or(ReplayError::ParseError)? == "1";
+            let player_2 = next_field(data.next(), line_number, "player 2")? == "1";
 
             self.clicks.push(Click::from_hold(frame, hold, player_2));
 
@@ -40,8 +34,8 @@ impl Replay {
         let mut writer = std::io::BufWriter::new(writer);
 
         writer
-            .write_all(format!("{}\n", self.fps).as_bytes())
-            .map_err(|_| ReplayError::WriteError)?;
+            .write_all(format!("{}\n", self.tps).as_bytes())
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
@@ -52,6 +46,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -0,0 +1,707 @@
+use std::{
+    io::{Read, Seek, Write},
+    rc::Rc,
+};
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::Format;
+
+use super::replay::{GameVersion, MetadataField, Replay, ReplayError};
+
+/// Anything a replay can be parsed from.
+pub trait ReplayReader: Read + Seek {}
+
+impl<T: Read + Seek> ReplayReader for T {}
+
+/// Anything a replay can be written to. Writers need to be readable, as some
+/// formats (yBot) patch their header after the inputs are written.
+pub trait ReplayWriter: Read + Write + Seek {}
+
+impl<T: Read + Write + Seek> ReplayWriter for T {}
+
+/// What a format is able to store, used to warn about lossy conversions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// Inputs of the second player in dual mode.
+    pub two_player: bool,
+    /// Inputs on buttons other than jump (platformer left/right).
+    pub buttons: bool,
+    /// FPS changes in the middle of the replay.
+    pub fps_changes: bool,
+    /// Metadata fields the format stores.
+    pub metadata: &'static [MetadataField],
+    /// How much of the per-input physics data the format stores.
+    pub physics: PhysicsSupport,
+    /// The FPS is stored or read back as a whole number.
+    pub integer_fps: bool,
+    /// Rows where neither player does anything are kept.
+    pub skip_rows: bool,
+    /// The game speed the replay was recorded at. Other formats get it
+    /// baked into their FPS.
+    pub speedhack: bool,
+    /// The exact game version the replay was recorded on, as long as it's
+    /// of the same update as the format's own.
+    pub game_version: bool,
+}
+
+/// How much of [`Physics`](super::replay::Physics) a format stores.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PhysicsSupport {
+    #[default]
+    None,
+    /// Only the X position.
+    XPosition,
+    /// X and Y positions and rotation.
+    PositionAndRotation,
+    /// Only physics records read from Echo, written back as they were.
+    EchoRecords,
+    /// Only physics records read from Fembot, written back as they were.
+    FembotRecords,
+    Full,
+}
+
+/// How a format stores its data.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Binary,
+    Json,
+    Text,
+}
+
+impl Encoding {
+    #[must_use]
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Binary => "application/octet-stream",
+            Self::Json => "application/json",
+            Self::Text => "text/plain",
+        }
+    }
+}
+
+/// A replay format the converter can load and save.
+pub trait ReplayFormat {
+    /// Name shown to users. Formats are looked up by it, so it has to be
+    /// unique within a registry.
+    fn name(&self) -> &str;
+
+    /// File extensions without the leading dot, most common first.
+    fn extensions(&self) -> &[&str];
+
+    fn capabilities(&self) -> Capabilities;
+
+    fn encoding(&self) -> Encoding {
+        Encoding::Binary
+    }
+
+    /// Game version the bot records replays for. Formats that store the
+    /// game version save this one when the replay is of another update.
+    fn game_version(&self) -> GameVersion {
+        GameVersion::Any
+    }
+
+    /// Formats of bots that are no longer maintained, which the web UI hides
+    /// by default.
+    fn legacy(&self) -> bool {
+        false
+    }
+
+    /// Frame of the converter that the format's frame 0 is on. Bots that
+    /// count from the first physics step instead of from the level start
+    /// store every input one frame earlier, and have an origin of 1.
+    ///
+    /// Only the origins of [`Format::OmegaBot2`] and [`Format::Tasbot`] are known,
+    /// from the offset their parsers and writers used to apply. Every other
+    /// built-in format keeps the 0 the converter has always assumed, and
+    /// hasn't been checked against recordings of its bot.
+    fn frame_origin(&self) -> u32 {
+        0
+    }
+
+    fn parse(&self, replay: &mut Replay, reader: &mut dyn ReplayReader) -> Result<(), ReplayError>;
+
+    fn write(&self, replay: &Replay, writer: &mut dyn ReplayWriter) -> Result<(), ReplayError>;
+}
+
+type ParseFn = fn(&mut Replay, &mut dyn ReplayReader) -> Result<(), ReplayError>;
+type WriteFn = fn(&Replay, &mut dyn ReplayWriter) -> Result<(), ReplayError>;
+
+/// One of the formats built into the converter.
+#[derive(Clone, Copy)]
+pub struct BuiltinFormat {
+    pub format: Format,
+    name: &'static str,
+    extensions: &'static [&'static str],
+    capabilities: Capabilities,
+    encoding: Encoding,
+    game_version: GameVersion,
+    legacy: bool,
+    frame_origin: u32,
+    parse: ParseFn,
+    write: WriteFn,
+}
+
+impl ReplayFormat for BuiltinFormat {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    fn game_version(&self) -> GameVersion {
+        self.game_version
+    }
+
+    fn legacy(&self) -> bool {
+        self.legacy
+    }
+
+    fn frame_origin(&self) -> u32 {
+        self.frame_origin
+    }
+
+    fn parse(&self, replay: &mut Replay, reader: &mut dyn ReplayReader) -> Result<(), ReplayError> {
+        (self.parse)(replay, reader)
+    }
+
+    fn write(&self, replay: &Replay, writer: &mut dyn ReplayWriter) -> Result<(), ReplayError> {
+        (self.write)(replay, writer)
+    }
+}
+
+const JUMP_ONLY: Capabilities = Capabilities {
+    two_player: true,
+    buttons: false,
+    fps_changes: false,
+    metadata: &[],
+    physics: PhysicsSupport::None,
+    integer_fps: false,
+    skip_rows: false,
+    speedhack: false,
+    game_version: false,
+};
+
+const BUTTONS: Capabilities = Capabilities {
+    two_player: true,
+    buttons: true,
+    fps_changes: false,
+    metadata: &[],
+    physics: PhysicsSupport::None,
+    integer_fps: false,
+    skip_rows: false,
+    speedhack: false,
+    game_version: false,
+};
+
+const FPS_CHANGES: Capabilities = Capabilities {
+    two_player: true,
+    buttons: false,
+    fps_changes: true,
+    metadata: &[],
+    physics: PhysicsSupport::None,
+    integer_fps: false,
+    skip_rows: false,
+    speedhack: false,
+    game_version: false,
+};
+
+const BUTTONS_AND_FPS_CHANGES: Capabilities = Capabilities {
+    two_player: true,
+    buttons: true,
+    fps_changes: true,
+    metadata: &[],
+    physics: PhysicsSupport::None,
+    integer_fps: false,
+    skip_rows: false,
+    speedhack: false,
+    game_version: false,
+};
+
+const GDR_CAPABILITIES: Capabilities = Capabilities {
+    metadata: &[
+        MetadataField::Author,
+        MetadataField::Description,
+        MetadataField::LevelId,
+        MetadataField::LevelName,
+        MetadataField::BotName,
+        MetadataField::BotVersion,
+        MetadataField::Seed,
+        MetadataField::Coins,
+        MetadataField::Ldm,
+    ],
+    game_version: true,
+    ..BUTTONS
+};
+
+const CANONICAL_CAPABILITIES: Capabilities = Capabilities {
+    metadata: &MetadataField::ALL,
+    physics: PhysicsSupport::Full,
+    skip_rows: true,
+    speedhack: true,
+    game_version: true,
+    ..BUTTONS_AND_FPS_CHANGES
+};
+
+// In the same order as `Format`, so a format's index is its discriminant
+pub static BUILTIN_FORMATS: [BuiltinFormat; 27] = [
+    BuiltinFormat {
+        format: Format::OmegaBot,
+        name: "OmegaBot 3",
+        extensions: &["replay"],
+        capabilities: FPS_CHANGES,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_obot3(reader),
+        write: |replay, mut writer| replay.write_obot3(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::OmegaBot2,
+        name: "OmegaBot 2",
+        extensions: &["replay"],
+        capabilities: FPS_CHANGES,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 1,
+        parse: |replay, reader| replay.parse_obot2(reader),
+        write: |replay, mut writer| replay.write_obot2(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::URL,
+        name: "OmegaBot 1",
+        extensions: &["replay"],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_url(reader),
+        write: |replay, mut writer| replay.write_url(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::MHRJson,
+        name: "MHR (Json)",
+        extensions: &["mhr.json"],
+        capabilities: Capabilities {
+            physics: PhysicsSupport::PositionAndRotation,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Json,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_mhr_json(reader),
+        write: |replay, mut writer| replay.write_mhr_json(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::MHRBinary,
+        name: "MHR (Binary)",
+        extensions: &["mhr"],
+        capabilities: Capabilities {
+            integer_fps: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_mhr_binary(reader),
+        write: |replay, mut writer| replay.write_mhr_binary(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Tasbot,
+        name: "TASBot",
+        extensions: &["json"],
+        capabilities: Capabilities {
+            physics: PhysicsSupport::XPosition,
+            skip_rows: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Json,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 1,
+        parse: |replay, reader| replay.parse_tasbot(reader),
+        write: |replay, mut writer| replay.write_tasbot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::ZBot,
+        name: "zBot",
+        extensions: &["zbf"],
+        capabilities: Capabilities {
+            integer_fps: true,
+            speedhack: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_zbot(reader),
+        write: |replay, mut writer| replay.write_zbot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::ReplayBot,
+        name: "ReplayBot",
+        extensions: &["replay"],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_replaybot(reader),
+        write: |replay, mut writer| replay.write_replaybot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Fembot,
+        name: "Fembot",
+        extensions: &["freplay"],
+        capabilities: Capabilities {
+            physics: PhysicsSupport::FembotRecords,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_fembot(reader),
+        write: |replay, mut writer| replay.write_fembot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::EchoOld,
+        name: "Echo (Old, Json)",
+        extensions: &["echo"],
+        capabilities: Capabilities {
+            physics: PhysicsSupport::XPosition,
+            integer_fps: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Json,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_echo_old(reader),
+        write: |replay, mut writer| replay.write_echo_old(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::EchoNewJson,
+        name: "Echo (New, Json)",
+        extensions: &["echo"],
+        capabilities: Capabilities {
+            integer_fps: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Json,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_echo_new(reader),
+        write: |replay, mut writer| replay.write_echo_new(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::EchoNewBinary,
+        name: "Echo (New, Binary)",
+        extensions: &["echo"],
+        capabilities: Capabilities {
+            physics: PhysicsSupport::EchoRecords,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_echo_bin(reader),
+        write: |replay, mut writer| replay.write_echo_bin(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::YBot1,
+        name: "yBot 1",
+        extensions: &[],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_ybot1(reader),
+        write: |replay, mut writer| replay.write_ybot1(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::XBot,
+        name: "xBot",
+        extensions: &["xbot"],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Text,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_xbot(reader),
+        write: |replay, mut writer| replay.write_xbot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Rush,
+        name: "Rush",
+        extensions: &["rsh"],
+        capabilities: Capabilities {
+            integer_fps: true,
+            ..JUMP_ONLY
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_rush(reader),
+        write: |replay, mut writer| replay.write_rush(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::KDBot,
+        name: "KD-Bot",
+        extensions: &["kd"],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2113,
+        legacy: true,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_kdbot(reader),
+        write: |replay, mut writer| replay.write_kdbot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::YBot,
+        name: "yBot 2",
+        extensions: &["ybot"],
+        capabilities: Capabilities {
+            metadata: &[MetadataField::Created],
+            ..BUTTONS_AND_FPS_CHANGES
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_ybot2(reader),
+        write: |replay, mut writer| replay.write_ybot2(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::GDR,
+        name: "GDR",
+        extensions: &["gdr"],
+        capabilities: GDR_CAPABILITIES,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_gdr(reader),
+        write: |replay, mut writer| replay.write_gdr(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::GDRJson,
+        name: "GDR (Json)",
+        extensions: &["gdr.json"],
+        capabilities: GDR_CAPABILITIES,
+        encoding: Encoding::Json,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_gdr_json(reader),
+        write: |replay, mut writer| replay.write_gdr_json(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::XDBot,
+        name: "XDBot",
+        extensions: &["xd"],
+        capabilities: BUTTONS,
+        encoding: Encoding::Text,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_xdbot(reader),
+        write: |replay, mut writer| replay.write_xdbot(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Silicate,
+        name: "Silicate v1",
+        extensions: &["slc"],
+        capabilities: BUTTONS,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_silicate(reader),
+        write: |replay, mut writer| replay.write_silicate(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Slc2,
+        name: "Silicate v2",
+        extensions: &["slc"],
+        capabilities: BUTTONS_AND_FPS_CHANGES,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_slc2(reader),
+        write: |replay, mut writer| replay.write_slc2(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Slc3,
+        name: "Silicate v3",
+        extensions: &["slc"],
+        capabilities: Capabilities {
+            metadata: &[MetadataField::Seed],
+            game_version: true,
+            ..BUTTONS_AND_FPS_CHANGES
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_slc3(reader),
+        write: |replay, mut writer| replay.write_slc3(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Tcm,
+        name: "TCBot",
+        extensions: &["tcm"],
+        capabilities: Capabilities {
+            metadata: &[MetadataField::Seed],
+            ..BUTTONS_AND_FPS_CHANGES
+        },
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Version2206,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_tcm(reader),
+        write: |replay, mut writer| replay.write_tcm(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::PlainText,
+        name: "Plain Text",
+        extensions: &["txt"],
+        capabilities: JUMP_ONLY,
+        encoding: Encoding::Text,
+        game_version: GameVersion::Any,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_plain_text(reader),
+        write: |replay, mut writer| replay.write_plain_text(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::Canonical,
+        name: "Canonical",
+        extensions: &["obc"],
+        capabilities: CANONICAL_CAPABILITIES,
+        encoding: Encoding::Binary,
+        game_version: GameVersion::Any,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_canonical(reader),
+        write: |replay, mut writer| replay.write_canonical(&mut writer),
+    },
+    BuiltinFormat {
+        format: Format::CanonicalJson,
+        name: "Canonical (Json)",
+        extensions: &["obc.json"],
+        capabilities: CANONICAL_CAPABILITIES,
+        encoding: Encoding::Json,
+        game_version: GameVersion::Any,
+        legacy: false,
+        frame_origin: 0,
+        parse: |replay, reader| replay.parse_canonical_json(reader),
+        write: |replay, mut writer| replay.write_canonical_json(&mut writer),
+    },
+];
+
+// `Format::builtin` indexes by discriminant, so the table has to list every
+// format in the order of `Format`
+const _: () = {
+    assert!(BUILTIN_FORMATS.len() == Format::CanonicalJson as usize + 1);
+
+    let mut index = 0;
+    while index < BUILTIN_FORMATS.len() {
+        assert!(
+            BUILTIN_FORMATS[index].format as usize == index,
+            "BUILTIN_FORMATS is not in the order of Format"
+        );
+        index += 1;
+    }
+};
+
+impl Format {
+    /// The built-in implementation of this format.
+    #[must_use]
+    pub fn builtin(self) -> &'static BuiltinFormat {
+        &BUILTIN_FORMATS[self as usize]
+    }
+}
+
+/// The set of formats a converter can load and save. Starts out with every
+/// built-in format, more can be registered by crates using the converter.
+#[derive(Clone)]
+pub struct FormatRegistry {
+    formats: Vec<Rc<dyn ReplayFormat>>,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self {
+            formats: BUILTIN_FORMATS
+                .iter()
+                .map(|builtin| Rc::new(*builtin) as Rc<dyn ReplayFormat>)
+                .collect(),
+        }
+    }
+}
+
+impl FormatRegistry {
+    /// Adds a format, replacing any registered format with the same name.
+    pub fn register(&mut self, format: impl ReplayFormat + 'static) {
+        let format: Rc<dyn ReplayFormat> = Rc::new(format);
+
+        match self.formats.iter_mut().find(|f| f.name() == format.name()) {
+            Some(existing) => *existing = format,
+            None => self.formats.push(format),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Rc<dyn ReplayFormat>> {
+        self.formats
+            .iter()
+            .find(|format| format.name() == name)
+            .cloned()
+    }
+
+    /// Every registered format using the given extension (without the dot).
+    pub fn by_extension<'a>(
+        &'a self,
+        extension: &'a str,
+    ) -> impl Iterator<Item = &'a dyn ReplayFormat> + 'a {
+        self.formats
+            .iter()
+            .filter(move |format| {
+                format
+                    .extensions()
+                    .iter()
+                    .any(|ext| ext.eq_ignore_ascii_case(extension))
+            })
+            .map(AsRef::as_ref)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ReplayFormat> {
+        self.formats.iter().map(AsRef::as_ref)
+    }
+}
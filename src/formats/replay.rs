@@ -5,48 +5,286 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::Settings;
 
+use super::diagnostic::{Location, ParseDiagnostic};
+
 #[derive(Debug, Error)]
 pub enum ReplayError {
-    #[error("Failed to parse replay")]
-    ParseError,
+    #[error("Failed to parse replay: {0}")]
+    ParseError(Box<ParseDiagnostic>),
 
-    #[error("Failed to read to buffer")]
+    #[error("Failed to read or write replay data: {0}")]
     BufferError(#[from] std::io::Error),
 
-    #[error("Failed to write replay")]
-    WriteError,
+    #[error("Failed to write replay: {0}")]
+    WriteError(String),
 
-    #[error("Failed to read replay")]
+    #[error("Invalid Silicate v2 replay: {0}")]
     Slc2Error(#[from] slc_oxide::replay::ReplayError),
 
-    #[error("Failed to read replay")]
+    #[error("Invalid Silicate v3 replay: {0}")]
     Slc3Error(#[from] slc_oxide::v3::replay::ReplayError),
 }
 
+impl From<ParseDiagnostic> for ReplayError {
+    fn from(value: ParseDiagnostic) -> Self {
+        Self::ParseError(Box::new(value))
+    }
+}
+
+impl ReplayError {
+    fn truncated(format: &str, eof_at: Option<u64>) -> Self {
+        let diagnostic = ParseDiagnostic {
+            format: Some(format.to_string()),
+            location: eof_at.map(Location::Offset),
+            expected: Some("more data".to_string()),
+            found: Some("end of file".to_string()),
+            cause: None,
+        };
+
+        diagnostic.into()
+    }
+
+    /// Fills in what the parser of `format` couldn't know itself: the format
+    /// name, and where the input ended when it was cut short.
+    #[must_use]
+    pub fn located(self, format: &str, eof_at: Option<u64>) -> Self {
+        match self {
+            Self::ParseError(mut diagnostic) => {
+                diagnostic.format.get_or_insert_with(|| format.to_string());
+                Self::ParseError(diagnostic)
+            }
+            Self::BufferError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Self::truncated(format, eof_at)
+            }
+            Self::Slc2Error(slc_oxide::replay::ReplayError::IOError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Self::truncated(format, eof_at)
+            }
+            Self::Slc3Error(slc_oxide::v3::replay::ReplayError::IOError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Self::truncated(format, eof_at)
+            }
+            e => e,
+        }
+    }
+}
+
+/// Version of Geometry Dash a replay was recorded on. Every 2.1 version
+/// plays macros the same way, so they are all 2.113.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[wasm_bindgen]
 pub enum GameVersion {
     Any,
     Version2113,
+    Version2200,
+    Version2201,
+    Version2202,
+    Version2203,
+    Version2204,
+    Version2205,
     Version2206,
+    Version2207,
+}
+
+impl GameVersion {
+    /// Every 2.2 version, oldest first.
+    pub const V2_2: [Self; 8] = [
+        Self::Version2200,
+        Self::Version2201,
+        Self::Version2202,
+        Self::Version2203,
+        Self::Version2204,
+        Self::Version2205,
+        Self::Version2206,
+        Self::Version2207,
+    ];
+
+    /// Reads a version number like 2.204, or a build number without the dot
+    /// like 2204. Digits past the third decimal (2.2074) are ignored.
+    #[must_use]
+    pub fn from_number(number: f64) -> Option<Self> {
+        if !number.is_finite() || number < 1.0 {
+            return None;
+        }
+
+        // Moves the dot of build numbers after the first digit
+        let number = number / 10f64.powf(number.log10().floor());
+
+        match (number * 1000.0).round() as u32 {
+            2100..=2113 => Some(Self::Version2113),
+            thousandths @ 2200..=2207 => Some(Self::V2_2[(thousandths - 2200) as usize]),
+            _ => None,
+        }
+    }
+
+    /// The version number, like 2.204. `None` for [`Self::Any`].
+    #[must_use]
+    pub fn number(self) -> Option<f64> {
+        self.build().map(|build| f64::from(build) / 1000.0)
+    }
+
+    /// The version number without the dot, like 2204.
+    #[must_use]
+    pub fn build(self) -> Option<u32> {
+        match self {
+            Self::Any => None,
+            Self::Version2113 => Some(2113),
+            Self::Version2200 => Some(2200),
+            Self::Version2201 => Some(2201),
+            Self::Version2202 => Some(2202),
+            Self::Version2203 => Some(2203),
+            Self::Version2204 => Some(2204),
+            Self::Version2205 => Some(2205),
+            Self::Version2206 => Some(2206),
+            Self::Version2207 => Some(2207),
+        }
+    }
+
+    #[must_use]
+    pub fn is_2_2(self) -> bool {
+        Self::V2_2.contains(&self)
+    }
+
+    /// Whether both versions are known and belong to the same update, 2.1 or
+    /// 2.2.
+    #[must_use]
+    pub fn same_update(self, other: Self) -> bool {
+        self != Self::Any && other != Self::Any && self.is_2_2() == other.is_2_2()
+    }
 }
 
 impl Display for GameVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Any => write!(f, "Any"),
-            Self::Version2113 => write!(f, "2.113"),
-            Self::Version2206 => write!(f, "2.206"),
+        match self.build() {
+            Some(build) => write!(f, "{}.{:03}", build / 1000, build % 1000),
+            None => write!(f, "Any"),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct Replay {
-    pub fps: f32,
+    /// Initial physics ticks per second, see `fps_changes` for mid-replay
+    /// changes. Every frame of the replay is a tick, so this is the FPS of
+    /// 2.1 bots, which step physics once per rendered frame.
+    pub tps: f64,
+    /// Frames rendered per second. Only 2.2 runs physics at another rate,
+    /// and most formats don't store it, in which case it is the TPS.
+    pub fps: f64,
+    /// Game speed the replay was recorded at, each frame lasts
+    /// `speedhack / tps` seconds of game time.
+    pub speedhack: f32,
     pub clicks: Vec<Click>,
+    pub fps_changes: Vec<FpsChange>,
     pub game_version: GameVersion,
     pub settings: Settings,
+    pub metadata: ReplayMetadata,
+    /// Frames of inputs the parser left out because they are on a button
+    /// the converter doesn't know.
+    pub skipped_inputs: Vec<u32>,
+}
+
+/// Information about the replay itself rather than its inputs. Every field is
+/// optional, as most formats only store a few of them.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct ReplayMetadata {
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub level_id: Option<u32>,
+    pub level_name: Option<String>,
+    pub bot_name: Option<String>,
+    pub bot_version: Option<String>,
+    pub seed: Option<u64>,
+    pub coins: Option<i32>,
+    pub ldm: Option<bool>,
+    /// UNIX timestamp of when the replay was created.
+    pub created: Option<i64>,
+}
+
+impl ReplayMetadata {
+    /// Turns empty strings into `None`, for formats that always store text
+    /// fields.
+    #[must_use]
+    pub fn non_empty(value: String) -> Option<String> {
+        (!value.is_empty()).then_some(value)
+    }
+
+    #[must_use]
+    pub fn has(&self, field: MetadataField) -> bool {
+        match field {
+            MetadataField::Author => self.author.is_some(),
+            MetadataField::Description => self.description.is_some(),
+            MetadataField::LevelId => self.level_id.is_some(),
+            MetadataField::LevelName => self.level_name.is_some(),
+            MetadataField::BotName => self.bot_name.is_some(),
+            MetadataField::BotVersion => self.bot_version.is_some(),
+            MetadataField::Seed => self.seed.is_some(),
+            MetadataField::Coins => self.coins.is_some(),
+            MetadataField::Ldm => self.ldm.is_some(),
+            MetadataField::Created => self.created.is_some(),
+        }
+    }
+
+    /// Fields that have a value.
+    #[must_use]
+    pub fn fields(&self) -> Vec<MetadataField> {
+        MetadataField::ALL
+            .into_iter()
+            .filter(|field| self.has(*field))
+            .collect()
+    }
+}
+
+/// A single field of [`ReplayMetadata`], used to describe which ones a format
+/// can store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataField {
+    Author,
+    Description,
+    LevelId,
+    LevelName,
+    BotName,
+    BotVersion,
+    Seed,
+    Coins,
+    Ldm,
+    Created,
+}
+
+impl MetadataField {
+    pub const ALL: [Self; 10] = [
+        Self::Author,
+        Self::Description,
+        Self::LevelId,
+        Self::LevelName,
+        Self::BotName,
+        Self::BotVersion,
+        Self::Seed,
+        Self::Coins,
+        Self::Ldm,
+        Self::Created,
+    ];
+}
+
+impl Display for MetadataField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Author => "author",
+            Self::Description => "description",
+            Self::LevelId => "level ID",
+            Self::LevelName => "level name",
+            Self::BotName => "bot name",
+            Self::BotVersion => "bot version",
+            Self::Seed => "seed",
+            Self::Coins => "coins",
+            Self::Ldm => "LDM",
+            Self::Created => "creation date",
+        };
+
+        write!(f, "{name}")
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -93,19 +331,158 @@ impl ClickType {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[wasm_bindgen]
+pub enum Button {
+    #[default]
+    Jump = 1,
+    Left = 2,
+    Right = 3,
+}
+
+impl TryFrom<i32> for Button {
+    type Error = ReplayError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Jump),
+            2 => Ok(Self::Left),
+            3 => Ok(Self::Right),
+            _ => Err(ParseDiagnostic::new()
+                .expected("button 1, 2 or 3")
+                .found(value)
+                .into()),
+        }
+    }
+}
+
+impl Button {
+    #[must_use]
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Self::Jump)
+    }
+}
+
+/// Player state at the moment an input happened, used by some bots for
+/// accuracy correction.
+///
+/// The fields come from named fields of a format, like the X position of old
+/// Echo replays or the `x`, `y` and `r` of MHR events, except for physics
+/// read from an Echo or Fembot record, see [`Self::is_verified`].
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[wasm_bindgen]
+pub struct Physics {
+    pub x: f64,
+    pub y: f64,
+    pub rotation: f64,
+    pub y_velocity: f64,
+    /// Data only the format it was read from understands, written back
+    /// when saving to that same format.
+    #[wasm_bindgen(skip)]
+    pub raw: Option<RawPhysics>,
+}
+
+/// Physics data as a format stored it, kept for saving back to it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RawPhysics {
+    /// The 28 bytes after the player byte of an Echo "DBG" action.
+    Echo([u8; 28]),
+    /// The 60 bytes after the frame of a Fembot action.
+    Fembot([u8; 60]),
+    /// The `a` field of an MHR JSON event, which MHR doesn't document.
+    Mhr { a: f64 },
+}
+
+impl Physics {
+    pub const BINARY_SIZE: usize = 20;
+
+    /// Reads x, y and rotation as `f32` followed by the y velocity as `f64`.
+    ///
+    /// Neither Echo nor Fembot publish their record layout, and this reading
+    /// was not checked against either bot. Physics read this way is marked
+    /// as unverified, so its fields are never written to another format.
+    #[must_use]
+    pub fn from_le_bytes(bytes: [u8; Self::BINARY_SIZE]) -> Self {
+        let f32_at =
+            |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut y_velocity = [0u8; 8];
+        y_velocity.copy_from_slice(&bytes[12..20]);
+
+        Self {
+            x: f32_at(0) as f64,
+            y: f32_at(4) as f64,
+            rotation: f32_at(8) as f64,
+            y_velocity: f64::from_le_bytes(y_velocity),
+            raw: None,
+        }
+    }
+
+    /// Keeps a physics record, with the fields guessed from its start.
+    #[must_use]
+    pub fn from_raw(raw: RawPhysics) -> Self {
+        let bytes = match &raw {
+            RawPhysics::Echo(bytes) => &bytes[..],
+            RawPhysics::Fembot(bytes) => &bytes[..],
+            RawPhysics::Mhr { .. } => {
+                return Self {
+                    raw: Some(raw),
+                    ..Self::default()
+                }
+            }
+        };
+        let mut known = [0u8; Self::BINARY_SIZE];
+        known.copy_from_slice(&bytes[..Self::BINARY_SIZE]);
+
+        Self {
+            raw: Some(raw),
+            ..Self::from_le_bytes(known)
+        }
+    }
+
+    /// The physics if its fields can be written to other formats, which
+    /// isn't the case when they were guessed from a record.
+    #[must_use]
+    pub fn verified(self) -> Option<Self> {
+        self.is_verified().then_some(self)
+    }
+}
+
+#[wasm_bindgen]
+impl Physics {
+    /// Whether the fields were read from named fields rather than decoded
+    /// from an Echo or Fembot record, whose layout isn't known.
+    #[must_use]
+    pub fn is_verified(&self) -> bool {
+        !matches!(self.raw, Some(RawPhysics::Echo(_) | RawPhysics::Fembot(_)))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 #[wasm_bindgen]
 pub struct Click {
     pub frame: u32,
     pub p1: ClickType,
     pub p2: ClickType,
+    pub button: Button,
+    #[wasm_bindgen(skip)]
+    pub p1_physics: Option<Physics>,
+    #[wasm_bindgen(skip)]
+    pub p2_physics: Option<Physics>,
 }
 
 impl Click {
     #[must_use]
     pub fn from_hold(frame: u32, hold: bool, player_2: bool) -> Self {
+        Self::from_button(frame, hold, player_2, Button::Jump)
+    }
+
+    #[must_use]
+    pub fn from_button(frame: u32, hold: bool, player_2: bool, button: Button) -> Self {
         Self {
             frame,
+            button,
+            p1_physics: None,
+            p2_physics: None,
             p1: if player_2 {
                 ClickType::Skip
             } else {
@@ -119,22 +496,70 @@ impl Click {
         }
     }
 
+    #[must_use]
+    pub fn with_physics(mut self, player_2: bool, physics: Option<Physics>) -> Self {
+        if player_2 {
+            self.p2_physics = physics;
+        } else {
+            self.p1_physics = physics;
+        }
+
+        self
+    }
+
+    #[must_use]
+    pub fn physics(&self, player_2: bool) -> Option<Physics> {
+        if player_2 {
+            self.p2_physics
+        } else {
+            self.p1_physics
+        }
+    }
+
+    /// Calls `f` for every jump input in this click. Inputs on other buttons
+    /// are skipped, use [`Click::apply_button`] for formats that can store them.
     pub fn apply_hold<F, E>(&self, mut f: F) -> Result<(), E>
     where
         F: FnMut(u32, bool, bool) -> Result<(), E>,
+    {
+        if !self.button.is_jump() {
+            return Ok(());
+        }
+
+        self.apply_button(|frame, hold, p2, _| f(frame, hold, p2))
+    }
+
+    pub fn apply_button<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(u32, bool, bool, Button) -> Result<(), E>,
     {
         if !self.p1.is_skip() {
-            f(self.frame, self.p1.is_click(), false)?;
+            f(self.frame, self.p1.is_click(), false, self.button)?;
         }
 
         if !self.p2.is_skip() {
-            f(self.frame, self.p2.is_click(), true)?;
+            f(self.frame, self.p2.is_click(), true, self.button)?;
         }
 
         Ok(())
     }
 }
 
+/// Switches the replay to a new tick rate starting at `frame`. Frames after
+/// the change keep counting from `frame`, each lasting `1 / fps` seconds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[wasm_bindgen]
+pub struct FpsChange {
+    pub frame: u32,
+    pub fps: f64,
+}
+
+#[derive(Clone, Copy)]
+pub enum ReplayEvent<'a> {
+    Click(&'a Click),
+    FpsChange(&'a FpsChange),
+}
+
 impl Default for Replay {
     fn default() -> Self {
         Self::new(60.0, GameVersion::Version2113, Settings::default())
@@ -143,17 +568,190 @@ impl Default for Replay {
 
 impl Replay {
     #[must_use]
-    pub fn new(fps: f32, game_version: GameVersion, settings: Settings) -> Self {
+    pub fn new(tps: f64, game_version: GameVersion, settings: Settings) -> Self {
         Self {
-            fps,
+            tps,
+            fps: tps,
+            speedhack: 1.0,
             clicks: vec![],
+            fps_changes: vec![],
             game_version,
             settings,
+            metadata: ReplayMetadata::default(),
+            skipped_inputs: vec![],
+        }
+    }
+
+    /// Sets the tick rate for formats that store a single rate, which is
+    /// then the render FPS as well.
+    pub fn set_tps(&mut self, tps: f64) {
+        self.tps = tps;
+        self.fps = tps;
+    }
+
+    /// Changes the tick rate without moving any inputs. The render FPS is
+    /// kept, unless it was the tick rate too (as in 2.1 replays), in which
+    /// case it changes along with it.
+    pub fn change_tps(&mut self, tps: f64) {
+        if (self.fps - self.tps).abs() < f64::EPSILON {
+            self.fps = tps;
+        }
+        self.tps = tps;
+    }
+
+    /// The version the replay was recorded on if it's a 2.2 version, or
+    /// 2.206 otherwise, for 2.2 formats that store the version.
+    #[must_use]
+    pub fn game_version_2_2(&self) -> GameVersion {
+        if self.game_version.is_2_2() {
+            self.game_version
+        } else {
+            GameVersion::Version2206
+        }
+    }
+
+    /// Whether the replay was recorded at a game speed other than 1x.
+    #[must_use]
+    pub fn has_speedhack(&self) -> bool {
+        (self.speedhack - 1.0).abs() >= f32::EPSILON
+    }
+
+    #[must_use]
+    pub fn has_physics(&self) -> bool {
+        self.clicks
+            .iter()
+            .any(|click| click.p1_physics.is_some() || click.p2_physics.is_some())
+    }
+
+    /// Adds an input on the button with id `button`, or notes it in
+    /// `skipped_inputs` if the button is unknown.
+    pub(crate) fn push_button(&mut self, frame: u32, hold: bool, player_2: bool, button: i32) {
+        match Button::try_from(button) {
+            Ok(button) => self
+                .clicks
+                .push(Click::from_button(frame, hold, player_2, button)),
+            Err(_) => self.skipped_inputs.push(frame),
+        }
+    }
+
+    /// Amount of inputs on buttons other than jump (platformer left/right).
+    #[must_use]
+    pub fn non_jump_inputs(&self) -> usize {
+        self.clicks
+            .iter()
+            .filter(|click| !click.button.is_jump())
+            .map(|click| (!click.p1.is_skip()) as usize + (!click.p2.is_skip()) as usize)
+            .sum()
+    }
+
+    /// Returns clicks and FPS changes merged in frame order. FPS changes come
+    /// before clicks on the same frame.
+    #[must_use]
+    pub fn events(&self) -> Vec<ReplayEvent<'_>> {
+        let mut events = Vec::with_capacity(self.clicks.len() + self.fps_changes.len());
+        let mut changes = self.fps_changes.iter().peekable();
+
+        for click in &self.clicks {
+            while let Some(change) = changes.next_if(|change| change.frame <= click.frame) {
+                events.push(ReplayEvent::FpsChange(change));
+            }
+
+            events.push(ReplayEvent::Click(click));
+        }
+
+        events.extend(changes.map(ReplayEvent::FpsChange));
+
+        events
+    }
+
+    /// The highest tick rate the replay ever reaches, and a function moving a
+    /// frame to the frame of that tick rate closest to the time it happens at.
+    fn retiming(&self) -> (f64, impl Fn(u32) -> u32) {
+        let mut changes = self.fps_changes.clone();
+        changes.sort_by_key(|change| change.frame);
+
+        let target_tps = changes
+            .iter()
+            .map(|change| change.fps)
+            .fold(self.tps, f64::max);
+
+        // (start frame, start time in seconds, tps)
+        let mut segments = vec![(0u32, 0.0f64, self.tps)];
+        for change in &changes {
+            let (start, time, tps) = segments[segments.len() - 1];
+            let time = time + change.frame.saturating_sub(start) as f64 / tps;
+            segments.push((change.frame, time, change.fps));
+        }
+
+        let retime = move |frame: u32| {
+            let idx = segments.partition_point(|(start, _, _)| *start <= frame);
+            let (start, time, tps) = segments[idx.saturating_sub(1)];
+            let time = time + (frame - start) as f64 / tps;
+
+            (time * target_tps).round() as u32
+        };
+
+        (target_tps, retime)
+    }
+
+    /// Where `frame` ends up in [`Self::retimed_without_fps_changes`].
+    #[must_use]
+    pub fn retimed_frame(&self, frame: u32) -> u32 {
+        let (_, retime) = self.retiming();
+        retime(frame)
+    }
+
+    /// Returns a copy of the replay without FPS changes, running at the highest
+    /// tick rate the replay ever reaches. Every click is moved to the frame closest
+    /// to the time it happened at in the original replay.
+    #[must_use]
+    pub fn retimed_without_fps_changes(&self) -> Self {
+        let (target_tps, retime) = self.retiming();
+
+        let clicks = self
+            .clicks
+            .iter()
+            .map(|click| Click {
+                frame: retime(click.frame),
+                ..*click
+            })
+            .collect();
+
+        let mut replay = Self {
+            tps: self.tps,
+            fps: self.fps,
+            speedhack: self.speedhack,
+            clicks,
+            fps_changes: vec![],
+            game_version: self.game_version,
+            settings: self.settings,
+            metadata: self.metadata.clone(),
+            skipped_inputs: self.skipped_inputs.clone(),
+        };
+        replay.change_tps(target_tps);
+
+        replay
+    }
+
+    /// Moves every click and FPS change by `offset` frames. Anything that
+    /// would end up before the first frame is put on frame 0.
+    pub fn shift_frames(&mut self, offset: i64) {
+        let shift = |frame: u32| (i64::from(frame) + offset).max(0) as u32;
+
+        for click in &mut self.clicks {
+            click.frame = shift(click.frame);
+        }
+        for change in &mut self.fps_changes {
+            change.frame = shift(change.frame);
         }
     }
 
     pub fn clear(&mut self) {
         self.clicks.clear();
-        self.fps = 60.0;
+        self.fps_changes.clear();
+        self.metadata = ReplayMetadata::default();
+        self.skipped_inputs.clear();
+        self.set_tps(60.0);
+        self.speedhack = 1.0;
     }
 }
@@ -1,9 +1,13 @@
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
+use super::diagnostic::ParseDiagnostic;
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 
 static REPLAYBOT_HEADER: [u8; 4] = [0x52, 0x50, 0x4C, 0x59];
 
+// Magic, version, frame identifier and fps
+const REPLAYBOT_HEADER_SIZE: u64 = 10;
+
 impl Replay {
     pub fn parse_replaybot(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         self.game_version = GameVersion::Version2113;
@@ -14,23 +18,31 @@ impl Replay {
         let mut buf = [0u8; 4];
         reader.read_exact(&mut buf)?;
         if buf != REPLAYBOT_HEADER {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::magic(0, &REPLAYBOT_HEADER, &buf).into());
         }
 
         reader.read_exact(&mut small_buf)?;
         let version = u8::from_le_bytes(small_buf);
         if version != 2 {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::new()
+                .at_offset(4)
+                .expected("version 2")
+                .found(format!("version {version}"))
+                .into());
         }
 
         reader.read_exact(&mut small_buf)?;
         let is_frame = u8::from_le_bytes(small_buf) == 1;
         if !is_frame {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::new()
+                .at_offset(5)
+                .expected("a frame based replay")
+                .found("an x position based replay")
+                .into());
         }
 
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -38,7 +50,7 @@ impl Replay {
             reader.seek(std::io::SeekFrom::Start(old_pos))?;
         }
 
-        let clicks_len = len / 5;
+        let clicks_len = len.saturating_sub(REPLAYBOT_HEADER_SIZE) / 5;
 
         self.clicks.reserve(clicks_len as usize);
         for _ in 0..clicks_len {
@@ -61,7 +73,7 @@ impl Replay {
         writer.write_all(&REPLAYBOT_HEADER)?;
         writer.write_all(&[2u8])?; // Version 2
         writer.write_all(&[1u8])?; // Frame macro identifier
-        writer.write_all(&self.fps.to_le_bytes())?;
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
@@ -73,6 +85,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -13,7 +13,7 @@ impl Replay {
         let mut buf = [0u8; 4];
 
         reader.read_exact(&mut mid_buf)?;
-        self.fps = i16::from_le_bytes(mid_buf) as f32;
+        self.set_tps(i16::from_le_bytes(mid_buf).into());
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -41,7 +41,7 @@ impl Replay {
     pub fn write_rush(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        writer.write_all(&(self.fps as i16).to_le_bytes())?;
+        writer.write_all(&(self.tps as i16).to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
@@ -53,6 +53,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -10,7 +10,8 @@ use slc_oxide::{
     },
 };
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Button, Click, FpsChange, GameVersion, Replay, ReplayError, ReplayEvent};
 
 struct Slc2Meta {
     reserved: [u8; 64],
@@ -20,7 +21,7 @@ impl Meta for Slc2Meta {
     fn from_bytes(bytes: &[u8]) -> Self {
         let mut reserved = [0; 64];
         reserved.copy_from_slice(&bytes[..64]);
-        Slc2Meta { reserved }
+        Self { reserved }
     }
 
     fn size() -> u64 {
@@ -34,6 +35,32 @@ impl Meta for Slc2Meta {
     }
 }
 
+impl From<Button> for ActionType {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::Jump => Self::Jump,
+            Button::Left => Self::Left,
+            Button::Right => Self::Right,
+        }
+    }
+}
+
+impl TryFrom<ActionType> for Button {
+    type Error = ReplayError;
+
+    fn try_from(value: ActionType) -> Result<Self, Self::Error> {
+        match value {
+            ActionType::Jump => Ok(Self::Jump),
+            ActionType::Left => Ok(Self::Left),
+            ActionType::Right => Ok(Self::Right),
+            other => Err(ParseDiagnostic::new()
+                .expected("a jump, left or right action")
+                .found(format!("{other:?}"))
+                .into()),
+        }
+    }
+}
+
 impl Replay {
     pub fn parse_silicate(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         self.game_version = GameVersion::Version2206;
@@ -42,7 +69,7 @@ impl Replay {
 
         let mut fps = [0; 8];
         reader.read_exact(&mut fps)?;
-        self.fps = f64::from_le_bytes(fps) as f32;
+        self.set_tps(f64::from_le_bytes(fps));
 
         let mut input_count = [0; 4];
         reader.read_exact(&mut input_count)?;
@@ -56,18 +83,12 @@ impl Replay {
 
             let state = u32::from_le_bytes(state);
 
-            let frame = (state >> 4) as u32;
+            let frame = state >> 4;
             let player_2 = (state & 0b1000) != 0;
-            match (state & 0b0110) >> 1 {
-                1 => {}
-                _ => {
-                    continue;
-                }
-            };
-
+            let button = ((state & 0b0110) >> 1) as i32;
             let down = (state & 0b0001) != 0;
 
-            self.clicks.push(Click::from_hold(frame, down, player_2));
+            self.push_button(frame, down, player_2, button);
         }
 
         Ok(())
@@ -79,19 +100,20 @@ impl Replay {
         let mut reader = BufReader::new(reader);
         let replay = slc_oxide::replay::Replay::<Slc2Meta>::read(&mut reader)?;
 
-        self.fps = replay.tps as f32;
+        self.set_tps(replay.tps);
         self.clicks.clear();
         self.clicks.reserve(replay.inputs.len());
 
         for click in replay.inputs {
             match click.data {
                 InputData::Player(p) => {
-                    if p.button != 1 {
-                        continue;
-                    }
-
-                    self.clicks
-                        .push(Click::from_hold(click.frame as u32, p.hold, p.player_2));
+                    self.push_button(click.frame as u32, p.hold, p.player_2, i32::from(p.button));
+                }
+                InputData::TPS(tps) => {
+                    self.fps_changes.push(FpsChange {
+                        frame: click.frame as u32,
+                        fps: tps,
+                    });
                 }
                 _ => {}
             }
@@ -101,35 +123,58 @@ impl Replay {
     }
 
     pub fn parse_slc3(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
-        self.game_version = GameVersion::Version2206;
-
         let mut reader = BufReader::new(reader);
         let replay = slc_oxide::v3::Replay::read(&mut reader)?;
 
-        self.fps = replay.metadata.tps as f32;
+        // Replays saved by older converters have a build of 1
+        self.game_version = GameVersion::from_number(replay.metadata.build.into())
+            .unwrap_or(GameVersion::Version2206);
+
+        self.set_tps(replay.metadata.tps);
+        self.metadata.seed = Some(replay.metadata.seed);
         self.clicks.clear();
         let action_atom = replay
             .atoms
             .atoms
             .iter()
             .find(|atom| atom.id() == AtomId::Action)
-            .ok_or(ReplayError::ParseError)?;
+            .ok_or_else(|| {
+                ParseDiagnostic::new()
+                    .expected("an action atom")
+                    .found("none")
+            })?;
 
         let AtomVariant::Action(actions) = action_atom else {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::new()
+                .expected("an action atom")
+                .found(format!("{:?}", action_atom.id()))
+                .into());
         };
 
         for action in &actions.actions {
-            if action.action_type == ActionType::Jump {
+            if action.action_type == ActionType::TPS {
+                self.fps_changes.push(FpsChange {
+                    frame: action.frame as u32,
+                    fps: action.tps,
+                });
+            } else if action.is_player() {
                 let Action {
                     frame,
                     player2,
                     holding,
+                    action_type,
                     ..
                 } = action;
 
-                self.clicks
-                    .push(Click::from_hold(*frame as u32, *holding, *player2));
+                match Button::try_from(*action_type) {
+                    Ok(button) => self.clicks.push(Click::from_button(
+                        *frame as u32,
+                        *holding,
+                        *player2,
+                        button,
+                    )),
+                    Err(_) => self.skipped_inputs.push(*frame as u32),
+                }
             }
         }
 
@@ -139,23 +184,36 @@ impl Replay {
     pub fn write_slc3(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        let mut replay = slc_oxide::v3::Replay::new(Metadata::new(self.fps as f64, 0, 1));
-        replay.metadata.tps = self.fps as f64;
+        let mut replay = slc_oxide::v3::Replay::new(Metadata::new(
+            self.tps,
+            self.metadata.seed.unwrap_or_default(),
+            self.game_version_2_2().build().unwrap_or_default(),
+        ));
+        replay.metadata.tps = self.tps;
 
         let mut atom = ActionAtom::new();
 
-        self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, player_2| {
-                let _ = atom.add_player_action(frame.into(), ActionType::Jump, hold, player_2);
+        self.events()
+            .into_iter()
+            .try_for_each(|event| match event {
+                ReplayEvent::Click(click) => click.apply_button(|frame, hold, player_2, button| {
+                    let _ = atom.add_player_action(frame.into(), button.into(), hold, player_2);
 
-                Ok::<(), ReplayError>(())
-            })
-        })?;
+                    Ok::<(), ReplayError>(())
+                }),
+                ReplayEvent::FpsChange(change) => {
+                    let _ = atom.add_tps_action(change.frame.into(), change.fps);
+
+                    Ok(())
+                }
+            })?;
 
         replay.add_atom(AtomVariant::Action(atom));
 
         replay.write(&mut writer)?;
 
+        writer.flush()?;
+
         Ok(())
     }
 
@@ -163,44 +221,62 @@ impl Replay {
         let mut writer = BufWriter::new(writer);
 
         let meta = Slc2Meta { reserved: [0; 64] };
-        let mut replay = slc_oxide::replay::Replay::<Slc2Meta>::new(self.fps as f64, meta);
+        let mut replay = slc_oxide::replay::Replay::<Slc2Meta>::new(self.tps, meta);
 
         replay.inputs.reserve(self.clicks.len());
 
-        self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, player_2| {
-                replay.add_input(
-                    frame as u64,
-                    InputData::Player(PlayerInput {
-                        hold,
-                        player_2,
-                        button: 1,
-                    }),
-                );
-
-                Ok::<(), ReplayError>(())
-            })
-        })?;
+        self.events()
+            .into_iter()
+            .try_for_each(|event| match event {
+                ReplayEvent::Click(click) => click.apply_button(|frame, hold, player_2, button| {
+                    replay.add_input(
+                        frame as u64,
+                        InputData::Player(PlayerInput {
+                            hold,
+                            player_2,
+                            button: button as u8,
+                        }),
+                    );
+
+                    Ok::<(), ReplayError>(())
+                }),
+                ReplayEvent::FpsChange(change) => {
+                    replay.add_input(change.frame as u64, InputData::TPS(change.fps));
+
+                    Ok(())
+                }
+            })?;
 
         replay.write(&mut writer)?;
 
+        writer.flush()?;
+
         Ok(())
     }
 
     pub fn write_silicate(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        writer.write_all(&(self.fps as f64).to_le_bytes())?;
-        writer.write_all(&(self.clicks.len() as u32).to_le_bytes())?;
+        let mut clicks_len = 0u32;
+        self.clicks.iter().try_for_each(|click| {
+            click.apply_button(|_, _, _, _| {
+                clicks_len += 1;
+
+                Ok::<(), ReplayError>(())
+            })
+        })?;
+
+        writer.write_all(&self.tps.to_le_bytes())?;
+        writer.write_all(&clicks_len.to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, p2| {
+            click.apply_button(|frame, hold, p2, button| {
                 let mut state: u32 = 0;
 
                 state |= frame << 4;
-                state |= if p2 { 1 } else { 0 } << 3;
-                state |= 1 << 1;
-                state |= if hold { 1 } else { 0 };
+                state |= u32::from(p2) << 3;
+                state |= (button as u32) << 1;
+                state |= u32::from(hold);
 
                 writer.write_all(&state.to_le_bytes())?;
 
@@ -208,6 +284,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -2,8 +2,9 @@ use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
 use serde::{Deserialize, Serialize};
 
-use crate::formats::replay::ClickType;
+use crate::formats::replay::{Button, ClickType, Physics};
 
+use super::diagnostic::ParseDiagnostic;
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 
 #[derive(Serialize, Deserialize)]
@@ -27,21 +28,41 @@ struct TasbotAction {
     pub x_position: f64,
 }
 
+impl TasbotAction {
+    fn new(click: ClickType, physics: Option<Physics>) -> Self {
+        Self {
+            x_position: physics
+                .and_then(Physics::verified)
+                .map_or(0.0, |physics| physics.x),
+            click: match click {
+                ClickType::Click => 1,
+                ClickType::Release => 2,
+                ClickType::Skip => 0,
+            },
+        }
+    }
+
+    fn physics(&self) -> Option<Physics> {
+        (self.click != 0).then_some(Physics {
+            x: self.x_position,
+            ..Default::default()
+        })
+    }
+}
+
 impl Replay {
     pub fn parse_tasbot(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         let reader = BufReader::new(reader);
 
         let replay: TasbotReplay =
-            simd_json::from_reader(reader).map_err(|_| ReplayError::ParseError)?;
+            simd_json::from_reader(reader).map_err(|e| ParseDiagnostic::simd_json(&e))?;
 
-        let offset = self.settings.auto_offset as u32;
-
-        self.fps = replay.fps;
+        self.set_tps(replay.fps.into());
         self.clicks = replay
             .clicks
             .into_iter()
             .map(|click| Click {
-                frame: click.frame + offset,
+                frame: click.frame,
                 p1: match click.player_1.click {
                     1 => ClickType::Click,
                     2 => ClickType::Release,
@@ -52,6 +73,9 @@ impl Replay {
                     2 => ClickType::Release,
                     _ => ClickType::Skip,
                 },
+                button: Button::Jump,
+                p1_physics: click.player_1.physics(),
+                p2_physics: click.player_2.physics(),
             })
             .collect();
         self.game_version = GameVersion::Version2113;
@@ -60,45 +84,34 @@ impl Replay {
     }
 
     pub fn write_tasbot(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
-        let writer = BufWriter::new(writer);
-
-        let offset = self.settings.auto_offset as u32;
+        let mut writer = BufWriter::new(writer);
 
         let clicks = self
             .clicks
             .iter()
+            .filter(|click| click.button.is_jump())
             .map(|click| TasbotClick {
-                frame: click.frame - offset,
-                player_1: TasbotAction {
-                    x_position: 0.0,
-                    click: match click.p1 {
-                        ClickType::Click => 1,
-                        ClickType::Release => 2,
-                        ClickType::Skip => 0,
-                    },
-                },
-                player_2: TasbotAction {
-                    x_position: 0.0,
-                    click: match click.p2 {
-                        ClickType::Click => 1,
-                        ClickType::Release => 2,
-                        ClickType::Skip => 0,
-                    },
-                },
+                frame: click.frame,
+                player_1: TasbotAction::new(click.p1, click.p1_physics),
+                player_2: TasbotAction::new(click.p2, click.p2_physics),
             })
             .collect::<Vec<TasbotClick>>();
 
         let replay = TasbotReplay {
-            fps: self.fps,
+            fps: self.tps as f32,
             clicks,
         };
 
         if self.settings.beautified_json {
-            serde_json::to_writer_pretty(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer_pretty(&mut writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         } else {
-            serde_json::to_writer(writer, &replay).map_err(|_| ReplayError::WriteError)?;
+            serde_json::to_writer(&mut writer, &replay)
+                .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         }
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -1,8 +1,28 @@
 use std::io::{Read, Seek, Write};
 
-use tcm::{input::InputCommand, replay::ReplaySerializer, DynamicReplay};
+use tcm::{input::InputCommand, meta::MetaV2, replay::ReplaySerializer, DynamicReplay};
 
-use crate::formats::replay::{Click, Replay, ReplayError};
+use crate::formats::replay::{Button, Click, FpsChange, Replay, ReplayError, ReplayEvent};
+
+impl From<tcm::input::PlayerButton> for Button {
+    fn from(value: tcm::input::PlayerButton) -> Self {
+        match value {
+            tcm::input::PlayerButton::Jump => Self::Jump,
+            tcm::input::PlayerButton::Left => Self::Left,
+            tcm::input::PlayerButton::Right => Self::Right,
+        }
+    }
+}
+
+impl From<Button> for tcm::input::PlayerButton {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::Jump => Self::Jump,
+            Button::Left => Self::Left,
+            Button::Right => Self::Right,
+        }
+    }
+}
 
 impl Replay {
     pub fn parse_tcm(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
@@ -11,45 +31,66 @@ impl Replay {
         let mut reader = std::io::BufReader::new(reader);
         let replay = DynamicReplay::from_reader(&mut reader)?;
 
-        self.fps = replay.meta.tps();
-        self.clicks = replay
-            .inputs
-            .iter()
-            .filter_map(|input| match &input.input {
-                tcm::input::Input::Vanilla(i) => {
-                    Some(Click::from_hold(input.frame as u32, i.push, i.player2))
-                }
-                _ => None,
-            })
-            .collect();
+        self.set_tps(replay.meta.tps().into());
+        self.metadata.seed = replay.meta.rng_seed();
+        for input in &replay.inputs {
+            match &input.input {
+                tcm::input::Input::Vanilla(i) => self.clicks.push(Click::from_button(
+                    input.frame as u32,
+                    i.push,
+                    i.player2,
+                    i.button.into(),
+                )),
+                tcm::input::Input::Tps(t) => self.fps_changes.push(FpsChange {
+                    frame: input.frame as u32,
+                    fps: t.tps.into(),
+                }),
+                _ => {}
+            }
+        }
 
         Ok(())
     }
 
     pub fn write_tcm(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
-        let meta = Box::new(tcm::meta::MetaV2::new(self.fps, 0, None));
+        let meta = MetaV2::new(self.tps as f32, 0, self.metadata.seed);
         let mut inputs = Vec::new();
 
-        self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, player_2| {
-                inputs.push(InputCommand::new(
-                    frame as u64,
-                    tcm::input::Input::Vanilla(tcm::input::VanillaInput {
-                        push: hold,
-                        player2: player_2,
-                        button: tcm::input::PlayerButton::Jump,
-                    }),
-                ));
-
-                Ok::<(), ReplayError>(())
-            })
-        })?;
-
-        let replay = DynamicReplay { meta, inputs };
+        self.events()
+            .into_iter()
+            .try_for_each(|event| match event {
+                ReplayEvent::Click(click) => click.apply_button(|frame, hold, player_2, button| {
+                    inputs.push(InputCommand::new(
+                        frame as u64,
+                        tcm::input::Input::Vanilla(tcm::input::VanillaInput {
+                            push: hold,
+                            player2: player_2,
+                            button: button.into(),
+                        }),
+                    ));
+
+                    Ok::<(), ReplayError>(())
+                }),
+                ReplayEvent::FpsChange(change) => {
+                    inputs.push(InputCommand::new(
+                        change.frame as u64,
+                        tcm::input::Input::Tps(tcm::input::TpsInput {
+                            tps: change.fps as f32,
+                        }),
+                    ));
+
+                    Ok(())
+                }
+            })?;
+
+        // Serializing a `DynamicReplay` panics, as its meta has no static version
+        let replay = tcm::Replay { meta, inputs };
         let mut writer = std::io::BufWriter::new(writer);
         replay
             .serialize(&mut writer)
-            .map_err(|_| ReplayError::WriteError)?;
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
+
+        writer.flush()?;
 
         Ok(())
     }
@@ -1,5 +1,6 @@
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
+use super::diagnostic::ParseDiagnostic;
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 
 #[derive(PartialEq)]
@@ -17,7 +18,11 @@ impl TryFrom<u8> for URLReplayType {
             0 => Ok(Self::XPos),
             1 => Ok(Self::Frames),
             2 => Ok(Self::Both),
-            _ => Err(ReplayError::ParseError),
+            _ => Err(ParseDiagnostic::new()
+                .at_offset(4)
+                .expected("replay type 0, 1 or 2")
+                .found(value)
+                .into()),
         }
     }
 }
@@ -38,7 +43,7 @@ impl Replay {
 
         let mut buf = [0u8; 4];
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
         self.game_version = GameVersion::Version2113;
 
         let mut small_buf = [0u8; 1];
@@ -69,7 +74,13 @@ impl Replay {
             let player_2 = state >> 1 == 1;
 
             let frame = match replay_type {
-                URLReplayType::XPos => return Err(ReplayError::ParseError),
+                URLReplayType::XPos => {
+                    return Err(ParseDiagnostic::new()
+                        .at_offset(4)
+                        .expected("a frame based replay")
+                        .found("an x position based replay")
+                        .into())
+                }
                 URLReplayType::Frames => {
                     reader.read_exact(&mut buf)?;
                     u32::from_le_bytes(buf)
@@ -90,7 +101,7 @@ impl Replay {
     pub fn write_url(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        writer.write_all(&self.fps.to_le_bytes())?;
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
         writer.write_all(&[URLReplayType::Frames.into()])?;
 
         self.clicks.iter().try_for_each(|click| {
@@ -104,6 +115,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
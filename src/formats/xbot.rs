@@ -1,3 +1,4 @@
+use super::diagnostic::{parse_field, ParseDiagnostic};
 use super::replay::{Click, GameVersion, Replay, ReplayError};
 use std::io::{BufRead, Read, Seek, Write};
 
@@ -7,34 +8,25 @@ impl Replay {
         let mut lines = reader.lines();
 
         self.game_version = GameVersion::Version2113;
-        self.fps = lines
-            .next()
-            .ok_or(ReplayError::ParseError)?
-            .map_err(|_| ReplayError::ParseError)?
-            .split(' ')
-            .nth(1)
-            .ok_or(ReplayError::ParseError)?
-            .parse::<f32>()
-            .map_err(|_| ReplayError::ParseError)?;
-
-        lines.skip(1).try_for_each(|line| {
-            let line = line.map_err(|_| ReplayError::ParseError)?;
+        let fps = lines.next().transpose()?;
+        self.set_tps(parse_field(
+            fps.as_deref().and_then(|fps| fps.split(' ').nth(1)),
+            1,
+            "FPS",
+        )?);
+
+        // The second line only says `frames`
+        lines.enumerate().skip(1).try_for_each(|(idx, line)| {
+            let line_number = idx + 2;
+            let line = line.map_err(|e| ParseDiagnostic::new().at_line(line_number).cause(e))?;
             let mut data = line.split_whitespace();
 
-            let state = data
-                .next()
-                .ok_or(ReplayError::ParseError)?
-                .parse::<u32>()
-                .map_err(|_| ReplayError::ParseError)?;
+            let state = parse_field::<u32>(data.next(), line_number, "state")?;
 
             let hold = state % 2 == 1;
             let p2 = state > 1;
 
-            let frame = data
-                .next()
-                .ok_or(ReplayError::ParseError)?
-                .parse::<u32>()
-                .map_err(|_| ReplayError::ParseError)?;
+            let frame = parse_field::<u32>(data.next(), line_number, "frame")?;
 
             self.clicks.push(Click::from_hold(frame, hold, p2));
 
@@ -48,20 +40,22 @@ impl Replay {
         let mut writer = std::io::BufWriter::new(writer);
 
         writer
-            .write_all(format!("fps: {}\n", self.fps).as_bytes())
-            .map_err(|_| ReplayError::WriteError)?;
+            .write_all(format!("fps: {}\n", self.tps).as_bytes())
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
         writer.write_all(b"frames\n")?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
                 writer.write_all(
-                    format!("{} {}\n", (hold as u8) | if p2 { 2 } else { 0 }, frame,).as_bytes(),
+                    format!("{} {}\n", (hold as u8) | if p2 { 2 } else { 0 }, frame).as_bytes(),
                 )?;
 
                 Ok::<(), ReplayError>(())
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
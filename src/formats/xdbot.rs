@@ -1,4 +1,5 @@
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::{next_field, parse_field, ParseDiagnostic};
+use super::replay::{GameVersion, Replay, ReplayError};
 use std::io::{BufRead, Read, Seek, Write};
 
 impl Replay {
@@ -7,35 +8,23 @@ impl Replay {
         let mut lines = reader.lines();
 
         self.game_version = GameVersion::Version2206;
-        self.fps = lines
-            .next()
-            .ok_or(ReplayError::ParseError)?
-            .map_err(|_| ReplayError::ParseError)?
-            .parse::<f32>()
-            .map_err(|_| ReplayError::ParseError)?;
-
-        lines.try_for_each(|line| {
-            let line = line.map_err(|_| ReplayError::ParseError)?;
-            let mut data = line.split('|');
+        let fps = lines.next().transpose()?;
+        self.set_tps(parse_field(fps.as_deref().map(str::trim), 1, "FPS")?);
 
-            let frame = data
-                .next()
-                .ok_or(ReplayError::ParseError)?
-                .parse::<u32>()
-                .map_err(|_| ReplayError::ParseError)?;
+        lines.enumerate().try_for_each(|(idx, line)| {
+            let line_number = idx + 2;
+            let line = line.map_err(|e| ParseDiagnostic::new().at_line(line_number).cause(e))?;
+            let mut data = line.split('|');
 
-            let hold = data.next().ok_or(ReplayError::ParseError)? == "1";
+            let frame = parse_field::<u32>(data.next(), line_number, "frame")?;
 
-            let btn = data.next().ok_or(ReplayError::ParseError)? == "1";
+            let hold = next_field(data.next(), line_number, "hold")? == "1";
 
-            if !btn {
-                // Probably a bug, skip to the next click
-                return Ok::<(), ReplayError>(());
-            }
+            let button = parse_field::<i32>(data.next(), line_number, "button")?;
 
-            let player_2 = data.next().ok_or(ReplayError::ParseError)? != "1";
+            let player_2 = next_field(data.next(), line_number, "player 1")? != "1";
 
-            self.clicks.push(Click::from_hold(frame, hold, player_2));
+            self.push_button(frame, hold, player_2, button);
 
             Ok::<(), ReplayError>(())
         })?;
@@ -47,19 +36,26 @@ impl Replay {
         let mut writer = std::io::BufWriter::new(writer);
 
         writer
-            .write_all(format!("{}\n", self.fps).as_bytes())
-            .map_err(|_| ReplayError::WriteError)?;
+            .write_all(format!("{}\n", self.tps).as_bytes())
+            .map_err(|e| ReplayError::WriteError(e.to_string()))?;
 
         self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, p2| {
+            click.apply_button(|frame, hold, p2, button| {
+                // The last column is set for player 1 inputs
                 writer.write_all(
-                    format!("{}|{}|{}|{}\n", frame, hold as i32, 1, p2 as i32).as_bytes(),
+                    format!(
+                        "{}|{}|{}|{}\n",
+                        frame, hold as i32, button as i32, !p2 as i32
+                    )
+                    .as_bytes(),
                 )?;
 
                 Ok::<(), ReplayError>(())
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -2,7 +2,28 @@ use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
 use ybot_fmt::{Action, Meta, PlayerButton, TimedAction};
 
-use super::replay::{Click, GameVersion, Replay, ReplayError};
+use super::diagnostic::ParseDiagnostic;
+use super::replay::{Button, Click, FpsChange, GameVersion, Replay, ReplayError, ReplayEvent};
+
+impl From<PlayerButton> for Button {
+    fn from(value: PlayerButton) -> Self {
+        match value {
+            PlayerButton::Jump => Self::Jump,
+            PlayerButton::Left => Self::Left,
+            PlayerButton::Right => Self::Right,
+        }
+    }
+}
+
+impl From<Button> for PlayerButton {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::Jump => Self::Jump,
+            Button::Left => Self::Left,
+            Button::Right => Self::Right,
+        }
+    }
+}
 
 static YBOT1_HEADER: [u8; 4] = [0x79, 0x62, 0x6F, 0x74];
 
@@ -10,9 +31,13 @@ impl Replay {
     pub fn parse_ybot2(&mut self, reader: impl Read + Seek) -> Result<(), ReplayError> {
         let mut replay = ybot_fmt::Macro::open(reader)?;
 
-        self.fps = replay.get(Meta::FPS)?;
+        self.set_tps(replay.get(Meta::FPS)?.into());
         self.game_version = GameVersion::Version2206;
 
+        // Missing meta fields are read as all 0xFF bytes
+        let date = replay.get(Meta::DATE)?;
+        self.metadata.created = (date >= 0).then_some(date);
+
         let mut frame = 0;
 
         // Apparently I can't trust yBot 2's PRESSES meta field
@@ -27,14 +52,14 @@ impl Replay {
 
             match action.action {
                 Action::Button(p1, hold, b) => {
-                    if b != PlayerButton::Jump {
-                        continue;
-                    }
-
-                    self.clicks.push(Click::from_hold(frame as u32, hold, !p1));
+                    self.clicks
+                        .push(Click::from_button(frame as u32, hold, !p1, b.into()));
                 }
-                Action::FPS(_) => {
-                    todo!();
+                Action::FPS(fps) => {
+                    self.fps_changes.push(FpsChange {
+                        frame: frame as u32,
+                        fps: fps.into(),
+                    });
                 }
             }
         }
@@ -45,25 +70,42 @@ impl Replay {
     pub fn write_ybot2(&self, writer: &mut (impl Read + Write + Seek)) -> Result<(), ReplayError> {
         let mut replay = ybot_fmt::Macro::create(writer)?;
 
-        replay.set(Meta::FPS, self.fps)?;
+        replay.set(Meta::FPS, self.tps as f32)?;
         replay.set(Meta::PRESSES, self.clicks.len() as u64)?;
         replay.set(Meta::TOTAL_PRESSES, self.clicks.len() as u64)?;
 
-        let mut last_frame = 0;
-
-        self.clicks.iter().try_for_each(|click| {
-            click.apply_hold(|frame, hold, p2| {
-                let delta = frame - last_frame;
-                last_frame = frame;
+        if let Some(date) = self.metadata.created {
+            replay.set(Meta::DATE, date)?;
+        }
 
-                replay.add(TimedAction::new(
-                    delta.into(),
-                    Action::Button(!p2, hold, PlayerButton::Jump),
-                ))?;
+        let mut last_frame = 0;
 
-                Ok::<(), ReplayError>(())
-            })
-        })?;
+        self.events()
+            .into_iter()
+            .try_for_each(|event| match event {
+                ReplayEvent::Click(click) => click.apply_button(|frame, hold, p2, button| {
+                    let delta = frame - last_frame;
+                    last_frame = frame;
+
+                    replay.add(TimedAction::new(
+                        delta.into(),
+                        Action::Button(!p2, hold, button.into()),
+                    ))?;
+
+                    Ok::<(), ReplayError>(())
+                }),
+                ReplayEvent::FpsChange(change) => {
+                    let delta = change.frame - last_frame;
+                    last_frame = change.frame;
+
+                    replay.add(TimedAction::new(
+                        delta.into(),
+                        Action::FPS(change.fps as f32),
+                    ))?;
+
+                    Ok(())
+                }
+            })?;
 
         Ok(())
     }
@@ -76,11 +118,11 @@ impl Replay {
         let mut buf = [0u8; 4];
         reader.read_exact(&mut buf)?;
         if buf != YBOT1_HEADER {
-            return Err(ReplayError::ParseError);
+            return Err(ParseDiagnostic::magic(0, &YBOT1_HEADER, &buf).into());
         }
 
         reader.read_exact(&mut buf)?;
-        self.fps = f32::from_le_bytes(buf);
+        self.set_tps(f32::from_le_bytes(buf).into());
 
         reader.read_exact(&mut buf)?;
         let clicks_len = i32::from_le_bytes(buf);
@@ -105,9 +147,18 @@ impl Replay {
     pub fn write_ybot1(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
+        let mut clicks_len = 0i32;
+        self.clicks.iter().try_for_each(|click| {
+            click.apply_hold(|_, _, _| {
+                clicks_len += 1;
+
+                Ok::<(), ReplayError>(())
+            })
+        })?;
+
         writer.write_all(&YBOT1_HEADER)?;
-        writer.write_all(&self.fps.to_le_bytes())?;
-        writer.write_all(&self.clicks.len().to_le_bytes())?;
+        writer.write_all(&(self.tps as f32).to_le_bytes())?;
+        writer.write_all(&clicks_len.to_le_bytes())?;
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
@@ -119,6 +170,8 @@ impl Replay {
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
@@ -15,7 +15,8 @@ impl Replay {
         reader.read_exact(&mut buf)?;
         let speedhack = f32::from_le_bytes(buf);
 
-        self.fps = (1.0 / (delta * speedhack)).round();
+        self.set_tps((1.0 / delta).round().into());
+        self.speedhack = speedhack;
 
         let old_pos = reader.stream_position()?;
         let len = reader.seek(std::io::SeekFrom::End(0))?;
@@ -39,8 +40,9 @@ impl Replay {
             reader.read_exact(&mut small_buf)?;
             let hold = small_buf[0] == 0x31;
 
+            // The last byte is set for player 1 inputs
             reader.read_exact(&mut small_buf)?;
-            let player_2 = small_buf[0] == 0x31;
+            let player_2 = small_buf[0] != 0x31;
 
             self.clicks
                 .push(Click::from_hold(frame as u32, hold, player_2));
@@ -52,19 +54,21 @@ impl Replay {
     pub fn write_zbot(&self, writer: &mut (impl Write + Seek)) -> Result<(), ReplayError> {
         let mut writer = BufWriter::new(writer);
 
-        writer.write_all(&((1.0 / self.fps).to_le_bytes()))?; // Delta
-        writer.write_all(&(1.0_f32.to_le_bytes()))?; // Speedhack
+        writer.write_all(&(((1.0 / self.tps) as f32).to_le_bytes()))?; // Delta
+        writer.write_all(&(self.speedhack.to_le_bytes()))?; // Speedhack
 
         self.clicks.iter().try_for_each(|click| {
             click.apply_hold(|frame, hold, p2| {
                 writer.write_all(&(frame as i32).to_le_bytes())?;
                 writer.write_all(&(if hold { 0x31_u8 } else { 0x30_u8 }).to_le_bytes())?;
-                writer.write_all(&(if p2 { 0x30_u8 } else { 0x31_u8 }).to_le_bytes())?;
+                writer.write_all(&(if p2 { 0x30_u8 } else { 0x31_u8 }).to_le_bytes())?; // Player 1
 
                 Ok::<(), ReplayError>(())
             })
         })?;
 
+        writer.flush()?;
+
         Ok(())
     }
 }
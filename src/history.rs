@@ -0,0 +1,474 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Click, FpsChange, GameVersion, Replay, ReplayMetadata};
+
+/// How many edits can be undone, older ones are forgotten.
+const MAX_HISTORY: usize = 100;
+
+/// Everything about a replay that isn't a list, kept whole in a [`Change`].
+#[derive(Clone, PartialEq)]
+pub struct Properties {
+    tps: f64,
+    fps: f64,
+    speedhack: f32,
+    game_version: GameVersion,
+    metadata: ReplayMetadata,
+}
+
+impl Properties {
+    fn of(replay: &Replay) -> Self {
+        Self {
+            tps: replay.tps,
+            fps: replay.fps,
+            speedhack: replay.speedhack,
+            game_version: replay.game_version,
+            metadata: replay.metadata.clone(),
+        }
+    }
+
+    fn restore(&self, replay: &mut Replay) {
+        replay.tps = self.tps;
+        replay.fps = self.fps;
+        replay.speedhack = self.speedhack;
+        replay.game_version = self.game_version;
+        replay.metadata = self.metadata.clone();
+    }
+}
+
+/// A single reversible change to a replay.
+#[derive(Clone)]
+pub enum Change {
+    /// `removed` clicks starting at `index` were replaced by `inserted`.
+    Clicks {
+        index: usize,
+        removed: Vec<Click>,
+        inserted: Vec<Click>,
+    },
+    FpsChanges {
+        before: Vec<FpsChange>,
+        after: Vec<FpsChange>,
+    },
+    Properties {
+        before: Box<Properties>,
+        after: Box<Properties>,
+    },
+}
+
+impl Change {
+    /// What changed from `before` to `after`. Only the part of the clicks
+    /// between the first and last one that differ is kept.
+    #[must_use]
+    pub fn between(before: &Replay, after: &Replay) -> Vec<Self> {
+        let mut changes = vec![];
+
+        let prefix = before
+            .clicks
+            .iter()
+            .zip(&after.clicks)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = before.clicks[prefix..]
+            .iter()
+            .rev()
+            .zip(after.clicks[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix + suffix < before.clicks.len().max(after.clicks.len()) {
+            changes.push(Self::Clicks {
+                index: prefix,
+                removed: before.clicks[prefix..before.clicks.len() - suffix].to_vec(),
+                inserted: after.clicks[prefix..after.clicks.len() - suffix].to_vec(),
+            });
+        }
+
+        if before.fps_changes != after.fps_changes {
+            changes.push(Self::FpsChanges {
+                before: before.fps_changes.clone(),
+                after: after.fps_changes.clone(),
+            });
+        }
+
+        let (properties_before, properties_after) = (Properties::of(before), Properties::of(after));
+        if properties_before != properties_after {
+            changes.push(Self::Properties {
+                before: Box::new(properties_before),
+                after: Box::new(properties_after),
+            });
+        }
+
+        changes
+    }
+
+    /// Runs `edit`, which may only change what isn't a list (TPS, FPS,
+    /// speedhack, game version and metadata), and returns what it changed.
+    /// Cheaper than [`Self::between`], as the clicks aren't copied.
+    pub fn of_properties(replay: &mut Replay, edit: impl FnOnce(&mut Replay)) -> Vec<Self> {
+        let before = Properties::of(replay);
+        edit(replay);
+        let after = Properties::of(replay);
+
+        if before == after {
+            return vec![];
+        }
+
+        vec![Self::Properties {
+            before: Box::new(before),
+            after: Box::new(after),
+        }]
+    }
+
+    /// Makes the change again.
+    pub fn apply(&self, replay: &mut Replay) {
+        match self {
+            Self::Clicks {
+                index,
+                removed,
+                inserted,
+            } => {
+                replay
+                    .clicks
+                    .splice(*index..index + removed.len(), inserted.iter().copied());
+            }
+            Self::FpsChanges { after, .. } => replay.fps_changes.clone_from(after),
+            Self::Properties { after, .. } => after.restore(replay),
+        }
+    }
+
+    /// Puts the replay back the way it was before the change.
+    pub fn revert(&self, replay: &mut Replay) {
+        match self {
+            Self::Clicks {
+                index,
+                removed,
+                inserted,
+            } => {
+                replay
+                    .clicks
+                    .splice(*index..index + inserted.len(), removed.iter().copied());
+            }
+            Self::FpsChanges { before, .. } => replay.fps_changes.clone_from(before),
+            Self::Properties { before, .. } => before.restore(replay),
+        }
+    }
+}
+
+/// One undoable step, made of every change of an edit or a group of edits.
+#[derive(Clone)]
+struct Entry {
+    label: String,
+    changes: Vec<Change>,
+}
+
+/// An edit in the history, oldest first.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct HistoryItem {
+    pub label: String,
+    /// Whether the edit was undone and can be redone.
+    pub undone: bool,
+}
+
+/// Undo and redo stacks of the edits made to a replay.
+#[derive(Clone, Default)]
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    /// Edits made since the outermost open group started.
+    group: Option<Entry>,
+    depth: usize,
+}
+
+impl History {
+    /// Adds an edit, or adds it to the open group. Edits that didn't change
+    /// anything aren't recorded.
+    pub fn record(&mut self, label: &str, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+
+        if let Some(group) = &mut self.group {
+            group.changes.extend(changes);
+            return;
+        }
+
+        self.push(Entry {
+            label: label.to_string(),
+            changes,
+        });
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.redo.clear();
+        self.undo.push(entry);
+
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Records every edit until the matching [`Self::end_group`] as a single
+    /// step. Groups can be nested, only the outermost one is kept.
+    pub fn begin_group(&mut self, label: &str) {
+        self.depth += 1;
+
+        if self.group.is_none() {
+            self.group = Some(Entry {
+                label: label.to_string(),
+                changes: vec![],
+            });
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+
+        if self.depth == 0 {
+            self.close_group();
+        }
+    }
+
+    fn close_group(&mut self) {
+        self.depth = 0;
+
+        if let Some(group) = self.group.take() {
+            if !group.changes.is_empty() {
+                self.push(group);
+            }
+        }
+    }
+
+    /// Reverts the last step, closing any open group first. Returns whether
+    /// there was anything to undo.
+    pub fn undo(&mut self, replay: &mut Replay) -> bool {
+        self.close_group();
+
+        let Some(entry) = self.undo.pop() else {
+            return false;
+        };
+        for change in entry.changes.iter().rev() {
+            change.revert(replay);
+        }
+        self.redo.push(entry);
+
+        true
+    }
+
+    /// Makes the last undone step again. Returns whether there was anything
+    /// to redo.
+    pub fn redo(&mut self, replay: &mut Replay) -> bool {
+        self.close_group();
+
+        let Some(entry) = self.redo.pop() else {
+            return false;
+        };
+        for change in &entry.changes {
+            change.apply(replay);
+        }
+        self.undo.push(entry);
+
+        true
+    }
+
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+            || self
+                .group
+                .as_ref()
+                .is_some_and(|group| !group.changes.is_empty())
+    }
+
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Every step that can be undone or redone, oldest first.
+    #[must_use]
+    pub fn items(&self) -> Vec<HistoryItem> {
+        let done = self.undo.iter().map(|entry| HistoryItem {
+            label: entry.label.clone(),
+            undone: false,
+        });
+        let undone = self.redo.iter().rev().map(|entry| HistoryItem {
+            label: entry.label.clone(),
+            undone: true,
+        });
+
+        done.chain(undone).collect()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, presses};
+
+    /// Runs `edit` on the replay and records it like the converter does.
+    fn edit(
+        history: &mut History,
+        replay: &mut Replay,
+        label: &str,
+        edit: impl FnOnce(&mut Replay),
+    ) {
+        let before = replay.clone();
+        edit(replay);
+        history.record(label, Change::between(&before, replay));
+    }
+
+    #[test]
+    fn changes_only_keep_the_edited_clicks() {
+        let before = presses(240.0, &[1, 2, 3, 4, 5]);
+        let after = presses(240.0, &[1, 2, 10, 11, 4, 5]);
+
+        let changes = Change::between(&before, &after);
+        let [Change::Clicks {
+            index,
+            removed,
+            inserted,
+        }] = changes.as_slice()
+        else {
+            panic!("expected a single click change");
+        };
+        assert_eq!(*index, 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(inserted.len(), 2);
+
+        let mut replay = before.clone();
+        changes[0].apply(&mut replay);
+        assert_eq!(frames(&replay.clicks), frames(&after.clicks));
+        changes[0].revert(&mut replay);
+        assert_eq!(frames(&replay.clicks), frames(&before.clicks));
+    }
+
+    #[test]
+    fn undo_and_redo_restore_every_part_of_the_replay() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[1, 2]);
+
+        edit(&mut history, &mut replay, "Edit", |replay| {
+            replay.clicks.remove(0);
+            replay.fps_changes.push(FpsChange {
+                frame: 2,
+                fps: 60.0,
+            });
+            replay.tps = 120.0;
+            replay.metadata.author = Some("author".to_string());
+        });
+
+        assert!(history.undo(&mut replay));
+        assert_eq!(frames(&replay.clicks), [1, 2]);
+        assert!(replay.fps_changes.is_empty());
+        assert!((replay.tps - 240.0).abs() < f64::EPSILON);
+        assert_eq!(replay.metadata.author, None);
+
+        assert!(history.redo(&mut replay));
+        assert_eq!(frames(&replay.clicks), [2]);
+        assert_eq!(replay.fps_changes.len(), 1);
+        assert!((replay.tps - 120.0).abs() < f64::EPSILON);
+        assert_eq!(replay.metadata.author.as_deref(), Some("author"));
+
+        assert!(!history.redo(&mut replay));
+    }
+
+    #[test]
+    fn new_edits_clear_the_redo_stack() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[1]);
+
+        edit(&mut history, &mut replay, "First", |replay| {
+            replay.clicks.clear();
+        });
+        history.undo(&mut replay);
+        assert!(history.can_redo());
+
+        edit(&mut history, &mut replay, "Second", |replay| {
+            replay.tps = 60.0;
+        });
+        assert!(!history.can_redo());
+        assert_eq!(history.items().len(), 1);
+    }
+
+    #[test]
+    fn edits_without_changes_are_not_recorded() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[1]);
+
+        edit(&mut history, &mut replay, "Nothing", |_| {});
+        history.record(
+            "Properties",
+            Change::of_properties(&mut replay, |replay| replay.tps = 240.0),
+        );
+
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn nested_groups_are_one_step() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[1, 2, 3]);
+
+        history.begin_group("Outer");
+        edit(&mut history, &mut replay, "A", |replay| {
+            replay.clicks.remove(0);
+        });
+        history.begin_group("Inner");
+        edit(&mut history, &mut replay, "B", |replay| {
+            replay.clicks.remove(0);
+        });
+        history.end_group();
+        assert!(history.can_undo());
+        history.end_group();
+
+        let items = history.items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "Outer");
+
+        history.undo(&mut replay);
+        assert_eq!(frames(&replay.clicks), [1, 2, 3]);
+    }
+
+    #[test]
+    fn undo_closes_an_open_group() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[1, 2]);
+
+        history.begin_group("Group");
+        edit(&mut history, &mut replay, "Edit", |replay| {
+            replay.clicks.clear();
+        });
+
+        assert!(history.undo(&mut replay));
+        assert_eq!(frames(&replay.clicks), [1, 2]);
+
+        // Edits after the undo aren't part of the closed group
+        edit(&mut history, &mut replay, "After", |replay| {
+            replay.clicks.clear();
+        });
+        history.end_group();
+        assert_eq!(history.items()[0].label, "After");
+    }
+
+    #[test]
+    fn oldest_edits_are_forgotten() {
+        let mut history = History::default();
+        let mut replay = presses(240.0, &[]);
+
+        for frame in 0..=MAX_HISTORY as u32 {
+            edit(&mut history, &mut replay, &frame.to_string(), |replay| {
+                replay.clicks.push(Click::from_hold(frame, true, false));
+            });
+        }
+
+        let items = history.items();
+        assert_eq!(items.len(), MAX_HISTORY);
+        assert_eq!(items[0].label, "1");
+
+        while history.undo(&mut replay) {}
+        assert_eq!(frames(&replay.clicks), [0]);
+    }
+}
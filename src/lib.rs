@@ -1,6 +1,8 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 // Missing const for fn is allowed because #[wasm_bindgen] requires non-const functions
+// Needless pass by value is allowed because #[wasm_bindgen] can't take Option<&str>
 #![allow(
+    clippy::needless_pass_by_value,
     clippy::missing_errors_doc,
     clippy::missing_const_for_fn,
     clippy::cast_lossless,
@@ -9,20 +11,51 @@
     clippy::cast_possible_wrap,
     clippy::cast_precision_loss,
     clippy::module_name_repetitions,
-    clippy::cargo_common_metadata
+    clippy::cargo_common_metadata,
+    clippy::multiple_crate_versions
 )]
 
+pub mod align;
+pub mod cps;
+pub mod detect;
+pub mod diff;
 pub mod formats;
-
-use std::io::Cursor;
-
-use formats::replay::{Click, ClickType, GameVersion, Replay};
+pub mod history;
+pub mod loss;
+pub mod merge;
+pub mod profile;
+pub mod resample;
+pub mod selection;
+pub mod workspace;
+
+#[cfg(test)]
+mod test_utils;
+
+use std::{borrow::Cow, io::Cursor, rc::Rc};
+
+use align::{AlignOptions, Alignment};
+use cps::{CPSRuleset, CPSViolation};
+use detect::FormatCandidate;
+use diff::{DiffOptions, ReplayDiff};
+use formats::descriptor::FormatDescriptor;
+use formats::diagnostic::{EofTracker, Location, ParseDiagnostic};
+use formats::registry::{FormatRegistry, ReplayFormat};
+use formats::replay::{Button, Click, ClickType, FpsChange, GameVersion, Replay, ReplayError};
+use history::{Change, History, HistoryItem};
+use loss::LossReport;
+use merge::MergeReport;
+use profile::ConversionProfile;
+use resample::{ResampleReport, RoundingStrategy, SpeedhackMode};
+use selection::{Players, Selection};
 use thiserror::Error;
 use wasm_bindgen::prelude::*;
+use workspace::Workspace;
 
 #[wasm_bindgen]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Settings {
+    /// Moves inputs to the frame origin of each format when loading and
+    /// saving, so formats that count frames differently line up.
     pub auto_offset: bool,
     pub beautified_json: bool,
 }
@@ -41,10 +74,19 @@ impl Default for Settings {
 pub struct Converter {
     loaded_replay: Replay,
     pub settings: Settings,
+    registry: FormatRegistry,
+    /// Enabled conversion profiles, at most one per pair of game versions.
+    profiles: Vec<ConversionProfile>,
+    history: History,
+    /// Inputs copied or cut from a replay, with frames counted from the
+    /// start of their selection. Kept when another replay is loaded.
+    clipboard: Vec<Click>,
+    /// Every other loaded replay, `loaded_replay` being the active one.
+    workspace: Workspace,
 }
 
 #[wasm_bindgen]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Format {
     OmegaBot,
     OmegaBot2,
@@ -71,15 +113,109 @@ pub enum Format {
     Slc3,
     Tcm,
     PlainText,
+    Canonical,
+    CanonicalJson,
 }
 
-#[wasm_bindgen]
 #[derive(Debug, Error)]
 pub enum ConverterError {
     #[error("Invalid data provided")]
     InvalidData,
+
+    #[error("Failed to parse replay: {0}")]
+    Parse(Box<ParseDiagnostic>),
+
+    #[error("Failed to write replay: {0}")]
+    Write(String),
+
+    #[error("Failed to read or write replay data: {0}")]
+    Io(std::io::Error),
+
+    #[error("Invalid Silicate v2 replay: {0}")]
+    Slc2(slc_oxide::replay::ReplayError),
+
+    #[error("Invalid Silicate v3 replay: {0}")]
+    Slc3(slc_oxide::v3::replay::ReplayError),
+
+    #[error("Unknown format {0}")]
+    UnknownFormat(String),
+
+    #[error("Unknown replay slot {0}")]
+    UnknownSlot(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
 }
 
+impl ConverterError {
+    /// Name of the error kind, used as the `name` of errors thrown to JS.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InvalidData => "InvalidDataError",
+            Self::Parse(_) => "ParseError",
+            Self::Write(_) => "WriteError",
+            Self::Io(_) => "IoError",
+            Self::Slc2(_) | Self::Slc3(_) => "SilicateError",
+            Self::UnknownFormat(_) => "UnknownFormatError",
+            Self::UnknownSlot(_) => "UnknownSlotError",
+            Self::InvalidArgument(_) => "InvalidArgumentError",
+        }
+    }
+}
+
+impl From<ReplayError> for ConverterError {
+    fn from(value: ReplayError) -> Self {
+        match value {
+            ReplayError::ParseError(diagnostic) => Self::Parse(diagnostic),
+            ReplayError::WriteError(e) => Self::Write(e),
+            ReplayError::BufferError(e) => Self::Io(e),
+            ReplayError::Slc2Error(e) => Self::Slc2(e),
+            ReplayError::Slc3Error(e) => Self::Slc3(e),
+        }
+    }
+}
+
+// Thrown to JS as an `Error` with the message and the error kind as its name.
+// Parse errors also get the diagnostic's fields as properties
+impl From<ConverterError> for JsValue {
+    fn from(value: ConverterError) -> Self {
+        let error = js_sys::Error::new(&value.to_string());
+        error.set_name(value.name());
+
+        if let ConverterError::Parse(diagnostic) = &value {
+            let (offset, line, column) = match diagnostic.location {
+                Some(Location::Offset(offset)) => (Some(offset as f64), None, None),
+                Some(Location::Line(line)) => (None, Some(line as f64), None),
+                Some(Location::LineColumn { line, column }) => {
+                    (None, Some(line as f64), Some(column as f64))
+                }
+                None => (None, None, None),
+            };
+
+            let properties = [
+                ("format", diagnostic.format.clone().map(Self::from)),
+                ("offset", offset.map(Self::from)),
+                ("line", line.map(Self::from)),
+                ("column", column.map(Self::from)),
+                ("expected", diagnostic.expected.clone().map(Self::from)),
+                ("found", diagnostic.found.clone().map(Self::from)),
+                ("cause", diagnostic.cause.clone().map(Self::from)),
+            ];
+
+            for (key, value) in properties {
+                if let Some(value) = value {
+                    // Setting a property on a fresh Error can't fail
+                    let _ = js_sys::Reflect::set(&error, &key.into(), &value);
+                }
+            }
+        }
+
+        error.into()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console, js_name = error)]
@@ -89,74 +225,283 @@ extern "C" {
     fn console_log(s: &str);
 }
 
-#[wasm_bindgen]
-#[derive(Clone, Copy)]
-pub enum CPSRule {
-    Rule15CPS,
-    Rule3CPF,
-    Rule45CP5C,
+// Outside of the browser (the CLI), messages go to stderr so they don't mix
+// with command output
+#[cfg(not(target_arch = "wasm32"))]
+fn console_error(s: &str) {
+    eprintln!("{s}");
 }
 
-#[wasm_bindgen]
-pub struct CPSViolation {
-    pub rule: CPSRule,
-    pub frame: u32,
-    pub cps: f64,
+#[cfg(not(target_arch = "wasm32"))]
+fn console_log(s: &str) {
+    eprintln!("{s}");
+}
+
+/// A saved replay and what its format couldn't store.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct SavedReplay {
+    pub data: Vec<u8>,
+    pub report: LossReport,
 }
 
 #[wasm_bindgen]
 impl Converter {
+    /// Loads the replay, keeping the current one if parsing fails.
     pub fn load(&mut self, data: Vec<u8>, fmt: Format) -> Result<(), ConverterError> {
-        let cursor = Cursor::new(data);
+        let format = self.format(fmt);
 
-        self.loaded_replay.clear();
-        self.loaded_replay.settings = self.settings;
+        self.load_with(&data, format.as_ref())
+    }
 
-        let result = match fmt {
-            Format::PlainText => self.loaded_replay.parse_plain_text(cursor),
-            Format::Tasbot => self.loaded_replay.parse_tasbot(cursor),
-            Format::ZBot => self.loaded_replay.parse_zbot(cursor),
-            Format::OmegaBot => self.loaded_replay.parse_obot3(cursor),
-            Format::OmegaBot2 => self.loaded_replay.parse_obot2(cursor),
-            Format::URL => self.loaded_replay.parse_url(cursor),
-            Format::MHRJson => self.loaded_replay.parse_mhr_json(cursor),
-            Format::MHRBinary => self.loaded_replay.parse_mhr_binary(cursor),
-            Format::YBot => self.loaded_replay.parse_ybot2(cursor),
-            Format::GDR => self.loaded_replay.parse_gdr(cursor),
-            Format::GDRJson => self.loaded_replay.parse_gdr_json(cursor),
-            Format::ReplayBot => self.loaded_replay.parse_replaybot(cursor),
-            Format::YBot1 => self.loaded_replay.parse_ybot1(cursor),
-            Format::EchoOld => self.loaded_replay.parse_echo_old(cursor),
-            Format::EchoNewJson => self.loaded_replay.parse_echo_new(cursor),
-            Format::EchoNewBinary => self.loaded_replay.parse_echo_bin(cursor),
-            Format::Rush => self.loaded_replay.parse_rush(cursor),
-            Format::KDBot => self.loaded_replay.parse_kdbot(cursor),
-            Format::XBot => self.loaded_replay.parse_xbot(cursor),
-            Format::XDBot => self.loaded_replay.parse_xdbot(cursor),
-            Format::Fembot => self.loaded_replay.parse_fembot(cursor),
-            Format::Silicate => self.loaded_replay.parse_silicate(cursor),
-            Format::Slc2 => self.loaded_replay.parse_slc2(cursor),
-            Format::Slc3 => self.loaded_replay.parse_slc3(cursor),
-            Format::Tcm => self.loaded_replay.parse_tcm(cursor),
-        };
+    #[must_use]
+    pub fn detect_format(data: &[u8], filename_hint: Option<String>) -> Vec<FormatCandidate> {
+        detect::detect(data, filename_hint.as_deref())
+    }
+
+    /// Name, extensions, game version and features of a built-in format.
+    #[must_use]
+    pub fn describe_format(fmt: Format) -> FormatDescriptor {
+        fmt.descriptor()
+    }
+
+    /// Descriptors of every built-in format, in the order of [`Format`].
+    #[must_use]
+    pub fn format_descriptors() -> Vec<FormatDescriptor> {
+        FormatDescriptor::builtin()
+    }
+
+    /// Loads the replay using the most likely detected format that parses.
+    /// Formats that parse without any inputs are only used as a last resort.
+    /// When none parse, fails with the error of the most likely format, or
+    /// with [`ConverterError::InvalidData`] when no format was detected.
+    pub fn load_auto(
+        &mut self,
+        data: &[u8],
+        filename_hint: Option<String>,
+    ) -> Result<Format, ConverterError> {
+        let mut fallback = None;
+        let mut error = None;
+
+        for candidate in detect::detect(data, filename_hint.as_deref()) {
+            let format = self.format(candidate.format);
+            let mut replay = Replay {
+                settings: self.settings,
+                ..Default::default()
+            };
+
+            if let Err(e) = Self::parse(&mut replay, data, format.as_ref()) {
+                error.get_or_insert(e);
+                continue;
+            }
 
-        match result {
-            Ok(()) => {}
-            Err(e) => {
-                console_error(&e.to_string());
+            if !replay.clicks.is_empty() {
+                self.loaded_replay = replay;
+                self.history.clear();
+                return Ok(candidate.format);
+            }
+
+            if fallback.is_none() {
+                fallback = Some((replay, candidate.format));
             }
         }
 
-        Ok(())
+        let (replay, format) = match (fallback, error) {
+            (Some(fallback), _) => fallback,
+            (None, Some(e)) => return Err(e.into()),
+            (None, None) => return Err(ConverterError::InvalidData),
+        };
+        self.loaded_replay = replay;
+        self.history.clear();
+
+        Ok(format)
     }
 
     #[must_use]
-    pub fn get_fps(&self) -> f32 {
+    pub fn get_tps(&self) -> f64 {
+        self.loaded_replay.tps
+    }
+
+    /// Changes the TPS without moving any inputs, which changes how fast the
+    /// replay plays back. Use [`Self::resample`] to keep its timing.
+    pub fn set_tps(&mut self, tps: f64) {
+        self.edit_properties("Change TPS", |replay| replay.change_tps(tps));
+    }
+
+    /// Frames rendered per second, which is the TPS unless the replay comes
+    /// from a 2.2 format that stores both.
+    #[must_use]
+    pub fn get_fps(&self) -> f64 {
         self.loaded_replay.fps
     }
 
-    pub fn set_fps(&mut self, fps: f32) {
-        self.loaded_replay.fps = fps;
+    /// Changes the render FPS, which doesn't affect when inputs happen.
+    pub fn set_fps(&mut self, fps: f64) {
+        self.edit_properties("Change FPS", |replay| replay.fps = fps);
+    }
+
+    #[must_use]
+    pub fn get_speedhack(&self) -> f32 {
+        self.loaded_replay.speedhack
+    }
+
+    /// Changes the speedhack without moving any inputs, use
+    /// [`Self::change_speedhack`] to keep the replay's timing.
+    pub fn set_speedhack(&mut self, speedhack: f32) {
+        self.edit_properties("Change speedhack", |replay| replay.speedhack = speedhack);
+    }
+
+    /// Re-times the replay as if it was recorded at another game speed,
+    /// either by moving inputs or by changing the FPS.
+    pub fn change_speedhack(
+        &mut self,
+        speedhack: f32,
+        mode: SpeedhackMode,
+        strategy: RoundingStrategy,
+    ) -> Result<ResampleReport, ConverterError> {
+        if !(speedhack.is_finite() && speedhack > 0.0) {
+            return Err(ConverterError::InvalidArgument(format!(
+                "can't change the speedhack to {speedhack}"
+            )));
+        }
+        if !(self.loaded_replay.speedhack.is_finite() && self.loaded_replay.speedhack > 0.0) {
+            return Err(ConverterError::InvalidArgument(format!(
+                "can't change a speedhack of {}",
+                self.loaded_replay.speedhack
+            )));
+        }
+
+        Ok(self.edit("Change speedhack", |replay| {
+            replay.change_speedhack(speedhack, mode, strategy)
+        }))
+    }
+
+    /// Re-times every input to `tps`, so the replay plays back the same.
+    pub fn resample(
+        &mut self,
+        tps: f64,
+        strategy: RoundingStrategy,
+    ) -> Result<ResampleReport, ConverterError> {
+        if !(tps.is_finite() && tps > 0.0) {
+            return Err(ConverterError::InvalidArgument(format!(
+                "can't resample to {tps} TPS"
+            )));
+        }
+        if !(self.loaded_replay.tps.is_finite() && self.loaded_replay.tps > 0.0) {
+            return Err(ConverterError::InvalidArgument(format!(
+                "can't resample a replay at {} TPS",
+                self.loaded_replay.tps
+            )));
+        }
+
+        Ok(self.edit("Resample", |replay| replay.resample(tps, strategy)))
+    }
+
+    #[must_use]
+    pub fn fps_changes(&self) -> Vec<FpsChange> {
+        self.loaded_replay.fps_changes.clone()
+    }
+
+    #[must_use]
+    pub fn get_author(&self) -> Option<String> {
+        self.loaded_replay.metadata.author.clone()
+    }
+
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.edit_properties("Change author", |replay| replay.metadata.author = author);
+    }
+
+    #[must_use]
+    pub fn get_description(&self) -> Option<String> {
+        self.loaded_replay.metadata.description.clone()
+    }
+
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.edit_properties("Change description", |replay| {
+            replay.metadata.description = description;
+        });
+    }
+
+    #[must_use]
+    pub fn get_level_id(&self) -> Option<u32> {
+        self.loaded_replay.metadata.level_id
+    }
+
+    pub fn set_level_id(&mut self, level_id: Option<u32>) {
+        self.edit_properties("Change level ID", |replay| {
+            replay.metadata.level_id = level_id;
+        });
+    }
+
+    #[must_use]
+    pub fn get_level_name(&self) -> Option<String> {
+        self.loaded_replay.metadata.level_name.clone()
+    }
+
+    pub fn set_level_name(&mut self, level_name: Option<String>) {
+        self.edit_properties("Change level name", |replay| {
+            replay.metadata.level_name = level_name;
+        });
+    }
+
+    #[must_use]
+    pub fn get_bot_name(&self) -> Option<String> {
+        self.loaded_replay.metadata.bot_name.clone()
+    }
+
+    pub fn set_bot_name(&mut self, bot_name: Option<String>) {
+        self.edit_properties("Change bot name", |replay| {
+            replay.metadata.bot_name = bot_name;
+        });
+    }
+
+    #[must_use]
+    pub fn get_bot_version(&self) -> Option<String> {
+        self.loaded_replay.metadata.bot_version.clone()
+    }
+
+    pub fn set_bot_version(&mut self, bot_version: Option<String>) {
+        self.edit_properties("Change bot version", |replay| {
+            replay.metadata.bot_version = bot_version;
+        });
+    }
+
+    #[must_use]
+    pub fn get_seed(&self) -> Option<u64> {
+        self.loaded_replay.metadata.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.edit_properties("Change seed", |replay| replay.metadata.seed = seed);
+    }
+
+    #[must_use]
+    pub fn get_coins(&self) -> Option<i32> {
+        self.loaded_replay.metadata.coins
+    }
+
+    pub fn set_coins(&mut self, coins: Option<i32>) {
+        self.edit_properties("Change coins", |replay| replay.metadata.coins = coins);
+    }
+
+    #[must_use]
+    pub fn get_ldm(&self) -> Option<bool> {
+        self.loaded_replay.metadata.ldm
+    }
+
+    pub fn set_ldm(&mut self, ldm: Option<bool>) {
+        self.edit_properties("Change LDM", |replay| replay.metadata.ldm = ldm);
+    }
+
+    #[must_use]
+    pub fn get_created(&self) -> Option<i64> {
+        self.loaded_replay.metadata.created
+    }
+
+    pub fn set_created(&mut self, created: Option<i64>) {
+        self.edit_properties("Change creation date", |replay| {
+            replay.metadata.created = created;
+        });
     }
 
     pub fn set_setting_beautify_json(&mut self, value: bool) {
@@ -192,23 +537,7 @@ impl Converter {
     }
 
     pub fn offset_all_by(&mut self, offset: i64) {
-        let clicks_old = self.loaded_replay.clicks.clone();
-
-        self.loaded_replay.clicks = clicks_old
-            .into_iter()
-            .map(|click| {
-                let new_frame = if offset < -(click.frame as i64) {
-                    0
-                } else {
-                    ((click.frame as i64) + offset) as u32
-                };
-
-                Click {
-                    frame: new_frame,
-                    ..click
-                }
-            })
-            .collect();
+        self.edit("Offset", |replay| replay.shift_frames(offset));
     }
 
     #[must_use]
@@ -221,45 +550,279 @@ impl Converter {
     }
 
     pub fn replace_frame_at(&mut self, idx: usize, frame: u32) {
-        self.loaded_replay.clicks[idx].frame = frame;
+        let click = Click {
+            frame,
+            ..self.loaded_replay.clicks[idx]
+        };
+
+        self.splice("Change frame", idx, 1, vec![click]);
     }
 
     pub fn insert_empty_at(&mut self, idx: usize, frame: u32) {
-        self.loaded_replay.clicks.insert(
-            idx,
-            Click {
-                frame,
-                p1: ClickType::Skip,
-                p2: ClickType::Skip,
-            },
-        );
+        let click = Click {
+            frame,
+            p1: ClickType::Skip,
+            p2: ClickType::Skip,
+            button: Button::Jump,
+            p1_physics: None,
+            p2_physics: None,
+        };
+
+        self.splice("Insert input", idx, 0, vec![click]);
     }
 
     pub fn remove_at(&mut self, idx: usize) {
-        self.loaded_replay.clicks.remove(idx);
+        self.splice("Remove input", idx, 1, vec![]);
     }
 
     pub fn toggle_click_at(&mut self, idx: usize, player_2: bool) {
+        let mut click = self.loaded_replay.clicks[idx];
         if player_2 {
-            self.loaded_replay.clicks[idx].p2 = self.loaded_replay.clicks[idx].p2.toggle();
+            click.p2 = click.p2.toggle();
         } else {
-            self.loaded_replay.clicks[idx].p1 = self.loaded_replay.clicks[idx].p1.toggle();
+            click.p1 = click.p1.toggle();
+        }
+
+        self.splice("Toggle input", idx, 1, vec![click]);
+    }
+
+    /// Removes the selected inputs. Returns how many rows were affected.
+    pub fn delete_range(&mut self, selection: Selection) -> usize {
+        self.edit("Delete range", |replay| replay.take_range(selection).len())
+    }
+
+    /// Moves the selected inputs by `offset` frames, keeping the inputs
+    /// sorted by frame.
+    pub fn shift_range(&mut self, selection: Selection, offset: i64) {
+        self.edit("Shift range", |replay| {
+            replay.shift_range(selection, offset);
+        });
+    }
+
+    /// Copies the selected inputs to the clipboard. Returns how many rows
+    /// were copied.
+    pub fn copy_range(&mut self, selection: Selection) -> usize {
+        self.clipboard = self.loaded_replay.copy_range(selection);
+        self.clipboard.len()
+    }
+
+    /// Moves the selected inputs to the clipboard. Returns how many rows
+    /// were cut.
+    pub fn cut_range(&mut self, selection: Selection) -> usize {
+        self.clipboard = self.edit("Cut", |replay| replay.take_range(selection));
+        self.clipboard.len()
+    }
+
+    /// Inserts the clipboard starting at `frame`, only for `players`.
+    pub fn paste_at(&mut self, frame: u32, players: Players) {
+        let clipboard = std::mem::take(&mut self.clipboard);
+        self.edit("Paste", |replay| replay.paste(&clipboard, frame, players));
+        self.clipboard = clipboard;
+    }
+
+    #[must_use]
+    pub fn clipboard_len(&self) -> usize {
+        self.clipboard.len()
+    }
+
+    /// Keeps player 1 of the loaded replay and takes player 2 from another
+    /// one, resampled to the loaded TPS if needed. Inputs of the other
+    /// player in either replay are dropped and reported as conflicts.
+    pub fn merge_players(
+        &mut self,
+        data: Vec<u8>,
+        fmt: Format,
+    ) -> Result<MergeReport, ConverterError> {
+        let other = self.read(&data, self.format(fmt).as_ref())?;
+
+        Ok(self.merge_players_of(other))
+    }
+
+    /// Places another replay from `frame` on, resampled to the loaded TPS if
+    /// needed. Loaded inputs on or after `frame` are dropped, and they and
+    /// buttons still held at `frame` are reported as conflicts.
+    pub fn append_replay(
+        &mut self,
+        data: Vec<u8>,
+        fmt: Format,
+        frame: u32,
+    ) -> Result<MergeReport, ConverterError> {
+        let other = self.read(&data, self.format(fmt).as_ref())?;
+
+        Ok(self.append(other, frame))
+    }
+
+    /// Names of every replay slot, in the order they were added.
+    #[must_use]
+    pub fn slots(&self) -> Vec<String> {
+        self.workspace.names()
+    }
+
+    /// The slot every editing method works on.
+    #[must_use]
+    pub fn active_slot(&self) -> String {
+        self.workspace.active().to_string()
+    }
+
+    /// Adds a slot with an empty replay.
+    pub fn add_slot(&mut self, name: &str) -> Result<(), ConverterError> {
+        if !self.workspace.add(name) {
+            return Err(ConverterError::InvalidArgument(format!(
+                "a slot named {name} already exists"
+            )));
         }
+
+        Ok(())
+    }
+
+    pub fn remove_slot(&mut self, name: &str) -> Result<(), ConverterError> {
+        if name == self.workspace.active() {
+            return Err(ConverterError::InvalidArgument(
+                "can't remove the active slot".to_string(),
+            ));
+        }
+
+        if !self.workspace.remove(name) {
+            return Err(ConverterError::UnknownSlot(name.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Makes `name` the slot every editing method works on. Each slot keeps
+    /// its own undo history.
+    pub fn set_active_slot(&mut self, name: &str) -> Result<(), ConverterError> {
+        if !self
+            .workspace
+            .activate(name, &mut self.loaded_replay, &mut self.history)
+        {
+            return Err(ConverterError::UnknownSlot(name.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Loads a replay into a slot, adding the slot if needed. The active slot
+    /// doesn't change, and a slot added for a replay that fails to load is
+    /// removed again.
+    pub fn load_slot(
+        &mut self,
+        name: &str,
+        data: Vec<u8>,
+        fmt: Format,
+    ) -> Result<(), ConverterError> {
+        let added = self.workspace.add(name);
+
+        let result = self.with_slot(name, |converter| converter.load(data, fmt))?;
+        // Don't leave behind an empty slot for a replay that didn't load
+        if result.is_err() && added {
+            self.workspace.remove(name);
+        }
+
+        result
+    }
+
+    pub fn save_slot(&mut self, name: &str, fmt: Format) -> Result<Vec<u8>, ConverterError> {
+        self.with_slot(name, |converter| converter.save(fmt))?
+    }
+
+    /// Replaces the replay in slot `to` with a copy of the one in `from`,
+    /// adding `to` if needed. The copy can be undone in `to`.
+    pub fn copy_slot(&mut self, from: &str, to: &str) -> Result<(), ConverterError> {
+        let replay = self.with_slot(from, |converter| converter.loaded_replay.clone())?;
+        self.workspace.add(to);
+
+        self.with_slot(to, |converter| {
+            converter.edit(&format!("Copy from {from}"), |loaded| *loaded = replay);
+        })
+    }
+
+    /// Like [`Self::merge_players`], taking player 2 from another slot.
+    pub fn merge_slot_players(&mut self, name: &str) -> Result<MergeReport, ConverterError> {
+        let other = self.with_slot(name, |converter| converter.loaded_replay.clone())?;
+
+        Ok(self.merge_players_of(other))
+    }
+
+    /// Like [`Self::append_replay`], appending the replay of another slot.
+    pub fn append_slot(&mut self, name: &str, frame: u32) -> Result<MergeReport, ConverterError> {
+        let other = self.with_slot(name, |converter| converter.loaded_replay.clone())?;
+
+        Ok(self.append(other, frame))
+    }
+
+    /// What changed from the loaded replay to another one.
+    pub fn diff_replay(
+        &self,
+        data: Vec<u8>,
+        fmt: Format,
+        options: DiffOptions,
+    ) -> Result<ReplayDiff, ConverterError> {
+        let other = self.read(&data, self.format(fmt).as_ref())?;
+
+        Ok(self.loaded_replay.diff(&other, options))
+    }
+
+    /// What changed from the replay in slot `old` to the one in `new`.
+    pub fn diff_slots(
+        &mut self,
+        old: &str,
+        new: &str,
+        options: DiffOptions,
+    ) -> Result<ReplayDiff, ConverterError> {
+        let old = self.with_slot(old, |converter| converter.loaded_replay.clone())?;
+
+        self.with_slot(new, |converter| old.diff(&converter.loaded_replay, options))
+    }
+
+    /// Estimates how to line the loaded replay up with a reference one, to
+    /// pass to [`Self::apply_alignment`].
+    pub fn estimate_alignment(
+        &self,
+        data: Vec<u8>,
+        fmt: Format,
+        options: AlignOptions,
+    ) -> Result<Alignment, ConverterError> {
+        let reference = self.read(&data, self.format(fmt).as_ref())?;
+
+        Ok(self.loaded_replay.estimate_alignment(&reference, options))
+    }
+
+    /// Like [`Self::estimate_alignment`], with the reference in a slot.
+    pub fn estimate_alignment_slot(
+        &mut self,
+        reference: &str,
+        options: AlignOptions,
+    ) -> Result<Alignment, ConverterError> {
+        let reference = self.with_slot(reference, |converter| converter.loaded_replay.clone())?;
+
+        Ok(self.loaded_replay.estimate_alignment(&reference, options))
+    }
+
+    /// Lines the replay up with the reference the alignment was estimated
+    /// against, see [`Replay::apply_alignment`].
+    pub fn apply_alignment(&mut self, alignment: &Alignment) {
+        self.edit("Align", |replay| replay.apply_alignment(alignment));
     }
 
     pub fn clean(&mut self) {
-        let mut current_click_state_p1 = false;
-        let mut current_click_state_p2 = false;
+        let before = self.loaded_replay.clone();
+
+        // Held state is tracked separately for jump, left and right
+        let mut click_states_p1 = [false; 3];
+        let mut click_states_p2 = [false; 3];
 
         let clicks_old = self.loaded_replay.clicks.clone();
         self.loaded_replay.clicks = clicks_old
             .into_iter()
             .map(|click| {
                 let mut new_click = click;
+                let current_click_state_p1 = &mut click_states_p1[click.button as usize - 1];
+                let current_click_state_p2 = &mut click_states_p2[click.button as usize - 1];
 
                 if click.p1.is_click() {
-                    let valid = !current_click_state_p1;
-                    current_click_state_p1 = true;
+                    let valid = !*current_click_state_p1;
+                    *current_click_state_p1 = true;
                     if !valid {
                         console_log(&format!(
                             "CLEANED {} - turned reduntant p1 click into skip",
@@ -268,8 +831,8 @@ impl Converter {
                         new_click.p1 = ClickType::Skip;
                     }
                 } else if click.p1.is_release() {
-                    let valid = current_click_state_p1;
-                    current_click_state_p1 = false;
+                    let valid = *current_click_state_p1;
+                    *current_click_state_p1 = false;
                     if !valid {
                         console_log(&format!(
                             "CLEANED {} - turned reduntant p1 release into skip",
@@ -280,8 +843,8 @@ impl Converter {
                 }
 
                 if click.p2.is_click() {
-                    let valid = !current_click_state_p2;
-                    current_click_state_p2 = if valid { true } else { current_click_state_p2 };
+                    let valid = !*current_click_state_p2;
+                    *current_click_state_p2 = true;
                     if !valid {
                         console_log(&format!(
                             "CLEANED {} - turned reduntant p2 click into skip",
@@ -290,8 +853,8 @@ impl Converter {
                         new_click.p2 = ClickType::Skip;
                     }
                 } else if click.p2.is_release() {
-                    let valid = current_click_state_p2;
-                    current_click_state_p2 = false;
+                    let valid = *current_click_state_p2;
+                    *current_click_state_p2 = false;
                     if !valid {
                         console_log(&format!(
                             "CLEANED {} - turned reduntant p2 release into skip",
@@ -317,17 +880,23 @@ impl Converter {
             .collect();
 
         console_log("Successfully cleaned replay");
+
+        self.record("Clean", &before);
     }
 
     pub fn sort(&mut self) {
-        self.loaded_replay
-            .clicks
-            .sort_by(|c1, c2| c1.frame.cmp(&c2.frame));
+        let before = self.loaded_replay.clone();
+
+        self.loaded_replay.clicks.sort_by_key(|c| c.frame);
 
         console_log("Successfully sorted inputs");
+
+        self.record("Sort", &before);
     }
 
     pub fn remove_all_player_inputs(&mut self, player_2: bool) {
+        let before = self.loaded_replay.clone();
+
         let clicks_old = self.loaded_replay.clicks.clone();
 
         self.loaded_replay.clicks = clicks_old
@@ -355,9 +924,18 @@ impl Converter {
                 true
             })
             .collect();
+
+        let label = if player_2 {
+            "Remove P2 inputs"
+        } else {
+            "Remove P1 inputs"
+        };
+        self.record(label, &before);
     }
 
     pub fn flip_p1_p2(&mut self) {
+        let before = self.loaded_replay.clone();
+
         let clicks_old = self.loaded_replay.clicks.clone();
 
         self.loaded_replay.clicks = clicks_old
@@ -371,9 +949,13 @@ impl Converter {
                 new_click
             })
             .collect();
+
+        self.record("Flip P1/P2", &before);
     }
 
     pub fn flip_up_down(&mut self) {
+        let before = self.loaded_replay.clone();
+
         let clicks_old = self.loaded_replay.clicks.clone();
 
         self.loaded_replay.clicks = clicks_old
@@ -395,149 +977,106 @@ impl Converter {
                 new_click
             })
             .collect();
-    }
-
-    pub fn save(&mut self, fmt: Format) -> Vec<u8> {
-        let buffer = Vec::new();
-        let mut cursor = Cursor::new(buffer);
-
-        self.loaded_replay.settings = self.settings;
-        if self.settings.beautified_json {
-            console_log("is beautified");
-        }
 
-        let result = match fmt {
-            Format::PlainText => self.loaded_replay.write_plain_text(&mut cursor),
-            Format::Tasbot => self.loaded_replay.write_tasbot(&mut cursor),
-            Format::ZBot => self.loaded_replay.write_zbot(&mut cursor),
-            Format::OmegaBot => self.loaded_replay.write_obot3(&mut cursor),
-            Format::OmegaBot2 => self.loaded_replay.write_obot2(&mut cursor),
-            Format::URL => self.loaded_replay.write_url(&mut cursor),
-            Format::MHRJson => self.loaded_replay.write_mhr_json(&mut cursor),
-            Format::MHRBinary => self.loaded_replay.write_mhr_binary(&mut cursor),
-            Format::YBot => self.loaded_replay.write_ybot2(&mut cursor),
-            Format::GDR => self.loaded_replay.write_gdr(&mut cursor),
-            Format::GDRJson => self.loaded_replay.write_gdr_json(&mut cursor),
-            Format::ReplayBot => self.loaded_replay.write_replaybot(&mut cursor),
-            Format::YBot1 => self.loaded_replay.write_ybot1(&mut cursor),
-            Format::EchoOld => self.loaded_replay.write_echo_old(&mut cursor),
-            Format::EchoNewJson => self.loaded_replay.write_echo_new(&mut cursor),
-            Format::EchoNewBinary => self.loaded_replay.write_echo_bin(&mut cursor),
-            Format::Rush => self.loaded_replay.write_rush(&mut cursor),
-            Format::KDBot => self.loaded_replay.write_kdbot(&mut cursor),
-            Format::XBot => self.loaded_replay.write_xbot(&mut cursor),
-            Format::XDBot => self.loaded_replay.write_xdbot(&mut cursor),
-            Format::Fembot => self.loaded_replay.write_fembot(&mut cursor),
-            Format::Silicate => self.loaded_replay.write_silicate(&mut cursor),
-            Format::Slc2 => self.loaded_replay.write_slc2(&mut cursor),
-            Format::Slc3 => self.loaded_replay.write_slc3(&mut cursor),
-            Format::Tcm => self.loaded_replay.write_tcm(&mut cursor),
-        };
-
-        match result {
-            Ok(()) => {}
-            Err(e) => {
-                console_error(&e.to_string());
-            }
-        }
+        self.record("Flip up/down", &before);
+    }
 
-        cursor.into_inner()
+    pub fn save(&mut self, fmt: Format) -> Result<Vec<u8>, ConverterError> {
+        self.save_with_report(fmt).map(|saved| saved.data)
     }
 
-    fn check_cps_for_player(&self, p2: bool) -> Vec<CPSViolation> {
-        let mut clicked_frames: Vec<u32> = vec![];
-        let mut violations = vec![];
+    /// Saves the replay along with what the format couldn't store.
+    pub fn save_with_report(&mut self, fmt: Format) -> Result<SavedReplay, ConverterError> {
+        let format = self.format(fmt);
 
-        self.loaded_replay.clicks.iter().for_each(|click| {
-            if p2 && !click.p2.is_click() {
-                return;
-            }
+        self.write(format.as_ref())
+    }
 
-            if !p2 && !click.p1.is_click() {
-                return;
-            }
+    /// What saving in `fmt` would drop or change, without saving.
+    #[must_use]
+    pub fn loss_report(&self, fmt: Format) -> LossReport {
+        self.prepare(self.format(fmt).as_ref()).1
+    }
 
-            let old_clicked_frames = clicked_frames.clone();
-            clicked_frames = old_clicked_frames
-                .into_iter()
-                .filter(|frame| click.frame - frame < (self.loaded_replay.fps as u32))
-                .collect();
+    /// Applies `profile` when saving a replay of its source version in a
+    /// format of its target version, replacing any profile enabled for the
+    /// same versions.
+    pub fn enable_profile(&mut self, profile: ConversionProfile) {
+        self.disable_profile(profile.from, profile.to);
+        self.profiles.push(profile);
+    }
 
-            clicked_frames.push(click.frame);
+    pub fn disable_profile(&mut self, from: GameVersion, to: GameVersion) {
+        self.profiles.retain(|profile| !profile.applies(from, to));
+    }
 
-            if clicked_frames.len() > 15 {
-                violations.push(CPSViolation {
-                    rule: CPSRule::Rule15CPS,
-                    frame: click.frame,
-                    cps: clicked_frames.len() as f64,
-                });
-            }
+    #[must_use]
+    pub fn profiles(&self) -> Vec<ConversionProfile> {
+        self.profiles.clone()
+    }
 
-            let just_this_frame = clicked_frames.iter().filter(|f| **f == click.frame);
+    /// Reverts the last edit. Returns whether there was anything to undo.
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(&mut self.loaded_replay)
+    }
 
-            if just_this_frame.count() > 3 {
-                violations.push(CPSViolation {
-                    rule: CPSRule::Rule3CPF,
-                    frame: click.frame,
-                    cps: clicked_frames.len() as f64,
-                });
-            }
+    /// Makes the last undone edit again. Returns whether there was anything
+    /// to redo.
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(&mut self.loaded_replay)
+    }
 
-            let max_frame_diff = (self.loaded_replay.fps / 45.0).ceil() as u32;
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
 
-            let mut last_5_clicks = clicked_frames.clone();
-            if last_5_clicks.len() < 6 {
-                return;
-            }
-            let last_5_clicks: Vec<u32> =
-                last_5_clicks.drain(0..(last_5_clicks.len() - 5)).collect();
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
 
-            let mut last_click: u32 = 0;
+    /// Every edit that can be undone or redone, oldest first.
+    #[must_use]
+    pub fn history(&self) -> Vec<HistoryItem> {
+        self.history.items()
+    }
 
-            let violates = last_5_clicks.iter().all(|c| {
-                let result = c - last_click < max_frame_diff;
-                last_click = *c;
+    /// Undoes every edit until the matching [`Self::end_group`] at once.
+    /// Groups can be nested, the outermost label is used.
+    pub fn begin_group(&mut self, label: &str) {
+        self.history.begin_group(label);
+    }
 
-                result
-            });
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
 
-            if violates {
-                violations.push(CPSViolation {
-                    rule: CPSRule::Rule45CP5C,
-                    frame: click.frame,
-                    cps: clicked_frames.len() as f64,
-                });
-            }
-        });
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
 
-        violations
+    /// Every click that breaks a rule of `ruleset`, player 1 first.
+    #[must_use]
+    pub fn cps_violations(&self, ruleset: &CPSRuleset) -> Vec<CPSViolation> {
+        ruleset.check(&self.loaded_replay)
     }
 
+    /// Logs every violation of the ILL CPS rules.
     pub fn check_cps(&self) {
-        let mut violations = self.check_cps_for_player(false);
-        let mut violations_p2 = self.check_cps_for_player(true);
-        violations.append(&mut violations_p2);
+        let ruleset = CPSRuleset::ill();
+        let violations = self.cps_violations(&ruleset);
 
         if violations.is_empty() {
-            console_log("No violations found. This macro complies with every ILL CPS rule.");
+            console_log(&format!(
+                "No violations found. This macro complies with every {} CPS rule.",
+                ruleset.name
+            ));
         }
 
-        violations
-            .iter()
-            .for_each(|violation| match violation.rule {
-                CPSRule::Rule15CPS => console_log(&format!(
-                    "FRAME {}: {} CPS! Exceeded 15 CPS",
-                    violation.frame, violation.cps
-                )),
-                CPSRule::Rule3CPF => console_log(&format!(
-                    "FRAME {}: {} CPS! Exceeded 3 clicks per frame",
-                    violation.frame, violation.cps
-                )),
-                CPSRule::Rule45CP5C => console_log(&format!(
-                    "FRAME {}: {} CPS! Exceeded 45 CPS in a burst of 5 inputs",
-                    violation.frame, violation.cps
-                )),
-            });
+        for violation in &violations {
+            console_log(&violation.to_string());
+        }
     }
 
     #[wasm_bindgen(constructor)]
@@ -547,6 +1086,250 @@ impl Converter {
     }
 }
 
+impl Converter {
+    #[must_use]
+    pub fn replay(&self) -> &Replay {
+        &self.loaded_replay
+    }
+
+    /// Like [`Self::merge_players`], with an already parsed replay.
+    pub fn merge_players_of(&mut self, other: Replay) -> MergeReport {
+        let report = self.edit("Merge players", |replay| replay.merge_players(other));
+
+        for conflict in &report.conflicts {
+            console_log(&conflict.to_string());
+        }
+
+        report
+    }
+
+    /// Like [`Self::append_replay`], with an already parsed replay.
+    pub fn append(&mut self, other: Replay, frame: u32) -> MergeReport {
+        let report = self.edit("Append replay", |replay| replay.append(other, frame));
+
+        for conflict in &report.conflicts {
+            console_log(&conflict.to_string());
+        }
+
+        report
+    }
+
+    /// The formats this converter can load and save.
+    #[must_use]
+    pub fn registry(&self) -> &FormatRegistry {
+        &self.registry
+    }
+
+    /// Adds a format that can then be used with [`Self::load_named`] and
+    /// [`Self::save_named`], replacing any format with the same name.
+    pub fn register_format(&mut self, format: impl ReplayFormat + 'static) {
+        self.registry.register(format);
+    }
+
+    /// Loads the replay using the registered format called `name`.
+    pub fn load_named(&mut self, data: &[u8], name: &str) -> Result<(), ConverterError> {
+        let format = self
+            .registry
+            .get(name)
+            .ok_or_else(|| ConverterError::UnknownFormat(name.to_string()))?;
+
+        self.load_with(data, format.as_ref())
+    }
+
+    /// Saves the replay using the registered format called `name`.
+    pub fn save_named(&mut self, name: &str) -> Result<Vec<u8>, ConverterError> {
+        self.save_named_with_report(name).map(|saved| saved.data)
+    }
+
+    /// Saves the replay using the registered format called `name`, along with
+    /// what the format couldn't store.
+    pub fn save_named_with_report(&mut self, name: &str) -> Result<SavedReplay, ConverterError> {
+        let format = self
+            .registry
+            .get(name)
+            .ok_or_else(|| ConverterError::UnknownFormat(name.to_string()))?;
+
+        self.write(format.as_ref())
+    }
+
+    fn load_with(&mut self, data: &[u8], format: &dyn ReplayFormat) -> Result<(), ConverterError> {
+        self.loaded_replay = self.read(data, format)?;
+        self.history.clear();
+
+        Ok(())
+    }
+
+    /// The registered implementation of a built-in format. Built-in formats
+    /// can be replaced, but never removed.
+    fn format(&self, fmt: Format) -> Rc<dyn ReplayFormat> {
+        let builtin = fmt.builtin();
+
+        self.registry
+            .get(builtin.name())
+            .unwrap_or_else(|| Rc::new(*builtin))
+    }
+
+    fn parse(
+        replay: &mut Replay,
+        data: &[u8],
+        format: &dyn ReplayFormat,
+    ) -> Result<(), ReplayError> {
+        let mut reader = EofTracker::new(Cursor::new(data));
+
+        format
+            .parse(replay, &mut reader)
+            .map_err(|e| e.located(format.name(), reader.eof_at()))?;
+
+        if !replay.skipped_inputs.is_empty() {
+            console_error(&format!(
+                "Skipped {} inputs on unknown buttons",
+                replay.skipped_inputs.len()
+            ));
+        }
+
+        if replay.settings.auto_offset {
+            replay.shift_frames(format.frame_origin().into());
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` with `name` as the active slot, then switches back.
+    fn with_slot<T>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> Result<T, ConverterError> {
+        let active = self.workspace.active().to_string();
+        self.set_active_slot(name)?;
+
+        let result = f(self);
+        self.set_active_slot(&active)?;
+
+        Ok(result)
+    }
+
+    /// Parses a replay with the current settings, without loading it.
+    fn read(&self, data: &[u8], format: &dyn ReplayFormat) -> Result<Replay, ConverterError> {
+        let mut replay = Replay {
+            settings: self.settings,
+            ..Default::default()
+        };
+        Self::parse(&mut replay, data, format)?;
+
+        Ok(replay)
+    }
+
+    /// Runs `edit` on the replay and records what it changed.
+    fn edit<T>(&mut self, label: &str, edit: impl FnOnce(&mut Replay) -> T) -> T {
+        let before = self.loaded_replay.clone();
+        let result = edit(&mut self.loaded_replay);
+        self.record(label, &before);
+
+        result
+    }
+
+    /// Records the changes from `before` to the current replay.
+    fn record(&mut self, label: &str, before: &Replay) {
+        self.history
+            .record(label, Change::between(before, &self.loaded_replay));
+    }
+
+    /// Like [`Self::edit`] for edits that don't touch clicks or FPS changes,
+    /// without copying them.
+    fn edit_properties(&mut self, label: &str, edit: impl FnOnce(&mut Replay)) {
+        let changes = Change::of_properties(&mut self.loaded_replay, edit);
+        self.history.record(label, changes);
+    }
+
+    /// Replaces `count` clicks at `index` with `inserted`, and records it.
+    fn splice(&mut self, label: &str, index: usize, count: usize, inserted: Vec<Click>) {
+        let removed = self
+            .loaded_replay
+            .clicks
+            .splice(index..index + count, inserted.iter().copied())
+            .collect();
+
+        self.history.record(
+            label,
+            vec![Change::Clicks {
+                index,
+                removed,
+                inserted,
+            }],
+        );
+    }
+
+    /// Origin the replay is shifted by when saving in `format`.
+    fn frame_origin(&self, format: &dyn ReplayFormat) -> u32 {
+        if self.settings.auto_offset {
+            format.frame_origin()
+        } else {
+            0
+        }
+    }
+
+    fn write(&mut self, format: &dyn ReplayFormat) -> Result<SavedReplay, ConverterError> {
+        self.loaded_replay.settings = self.settings;
+
+        let (replay, report) = self.prepare(format);
+        for loss in report.iter() {
+            console_error(&loss.to_string());
+        }
+
+        let mut cursor = Cursor::new(Vec::new());
+        format.write(&replay, &mut cursor)?;
+
+        Ok(SavedReplay {
+            data: cursor.into_inner(),
+            report,
+        })
+    }
+
+    /// The replay as it will be saved in `format`, along with everything
+    /// that changes on the way.
+    fn prepare(&self, format: &dyn ReplayFormat) -> (Cow<'_, Replay>, LossReport) {
+        let capabilities = format.capabilities();
+        let frame_origin = self.frame_origin(format);
+
+        let mut report = LossReport::new(&self.loaded_replay, capabilities, frame_origin);
+
+        let mut replay = Cow::Borrowed(&self.loaded_replay);
+        if !capabilities.fps_changes && !replay.fps_changes.is_empty() {
+            replay = Cow::Owned(replay.retimed_without_fps_changes());
+        }
+        if !capabilities.speedhack && replay.has_speedhack() {
+            replay.to_mut().change_speedhack(
+                1.0,
+                SpeedhackMode::KeepFrames,
+                RoundingStrategy::Nearest,
+            );
+        }
+
+        let from = replay.game_version;
+        let to = if capabilities.game_version && from.same_update(format.game_version()) {
+            from
+        } else {
+            format.game_version()
+        };
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.applies(from, to));
+        report.push_game_version(from, to, profile.is_some());
+        if let Some(profile) = profile {
+            let changes = profile.apply(replay.to_mut());
+            report.push_profile(profile, &changes);
+        }
+
+        if frame_origin > 0 {
+            replay.to_mut().shift_frames(-i64::from(frame_origin));
+        }
+
+        (replay, report)
+    }
+}
+
 #[wasm_bindgen(start)]
 fn run() {
     console_error_panic_hook::set_once();
@@ -0,0 +1,618 @@
+use std::fmt::Display;
+
+use wasm_bindgen::prelude::*;
+
+use crate::formats::{
+    registry::{Capabilities, PhysicsSupport},
+    replay::{Click, GameVersion, Physics, RawPhysics, Replay},
+};
+use crate::profile::ConversionProfile;
+
+/// How many affected frames a loss lists as examples.
+const EXAMPLE_FRAMES: usize = 5;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    /// Left/right inputs in a format that only stores jumps.
+    Buttons,
+    /// FPS changes re-timed into a single FPS.
+    FpsChanges,
+    /// Metadata fields the format has no place for.
+    Metadata,
+    /// A fractional FPS saved as a whole number.
+    FractionalFps,
+    /// Physics data the format has no place for.
+    Physics,
+    /// Rows where neither player does anything.
+    SkipRows,
+    /// A speedhack baked into the FPS.
+    Speedhack,
+    /// Inputs before the first frame the format can store, moved onto it.
+    FrameOrigin,
+    /// A format made for another game version than the replay.
+    GameVersion,
+    /// A conversion profile that re-timed the replay for another game
+    /// version.
+    Profile,
+    /// Inputs on unknown buttons, left out when the replay was loaded.
+    SkippedInputs,
+}
+
+/// One kind of data that a conversion drops or changes.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct Loss {
+    pub kind: LossKind,
+    /// Amount of inputs, FPS changes, rows or fields affected, or of
+    /// adjustments made by a profile.
+    pub count: usize,
+    /// Frames of the first few affected inputs. Empty when the loss isn't
+    /// tied to frames, like metadata.
+    pub frames: Vec<u32>,
+    pub message: String,
+}
+
+impl Display for Loss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if !self.frames.is_empty() {
+            let frames: Vec<String> = self.frames.iter().map(u32::to_string).collect();
+            let label = if frames.len() == 1 { "frame" } else { "frames" };
+            write!(f, " (e.g. {label} {})", frames.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything a format can't represent about a replay, so users can be
+/// warned before relying on the converted file.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct LossReport {
+    losses: Vec<Loss>,
+}
+
+#[wasm_bindgen]
+impl LossReport {
+    #[must_use]
+    pub fn losses(&self) -> Vec<Loss> {
+        self.losses.clone()
+    }
+
+    #[must_use]
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty()
+    }
+}
+
+/// Whether saving `physics` in a format with `support` loses any of it.
+/// Fields decoded from Echo and Fembot records are never written to other
+/// formats, as their layout isn't known.
+fn physics_lost(physics: &Physics, support: PhysicsSupport) -> bool {
+    match support {
+        PhysicsSupport::None => true,
+        PhysicsSupport::XPosition => {
+            physics.y != 0.0
+                || physics.rotation != 0.0
+                || physics.y_velocity != 0.0
+                || physics.raw.is_some()
+        }
+        PhysicsSupport::PositionAndRotation => !physics.is_verified() || physics.y_velocity != 0.0,
+        PhysicsSupport::EchoRecords => !matches!(physics.raw, Some(RawPhysics::Echo(_))),
+        PhysicsSupport::FembotRecords => !matches!(physics.raw, Some(RawPhysics::Fembot(_))),
+        PhysicsSupport::Full => false,
+    }
+}
+
+/// Amount of players with an input in `click`.
+fn inputs(click: &Click) -> usize {
+    (!click.p1.is_skip()) as usize + (!click.p2.is_skip()) as usize
+}
+
+impl LossReport {
+    /// Compares the replay against what a format with `capabilities` can
+    /// store, when saved with its frame 0 on `frame_origin`.
+    #[must_use]
+    pub fn new(replay: &Replay, capabilities: Capabilities, frame_origin: u32) -> Self {
+        let mut report = Self::default();
+
+        if !replay.skipped_inputs.is_empty() {
+            report.push(
+                LossKind::SkippedInputs,
+                replay.skipped_inputs.len(),
+                replay.skipped_inputs.iter().copied(),
+                format!(
+                    "Skipped {} inputs on unknown buttons when loading the replay",
+                    replay.skipped_inputs.len()
+                ),
+            );
+        }
+
+        if !capabilities.buttons {
+            report.push_clicks(
+                LossKind::Buttons,
+                replay,
+                |click| {
+                    if click.button.is_jump() {
+                        0
+                    } else {
+                        inputs(click)
+                    }
+                },
+                |count| {
+                    format!(
+                        "This format only supports jump inputs, dropped {count} left/right inputs"
+                    )
+                },
+            );
+        }
+
+        report.push_fps(replay, capabilities);
+
+        let metadata: Vec<String> = replay
+            .metadata
+            .fields()
+            .into_iter()
+            .filter(|field| !capabilities.metadata.contains(field))
+            .map(|field| field.to_string())
+            .collect();
+        if !metadata.is_empty() {
+            report.push(
+                LossKind::Metadata,
+                metadata.len(),
+                [],
+                format!("This format can't store the {}", metadata.join(", ")),
+            );
+        }
+
+        let physics = |click: &Click| {
+            [click.p1_physics, click.p2_physics]
+                .into_iter()
+                .flatten()
+                .filter(|physics| physics_lost(physics, capabilities.physics))
+                .count()
+        };
+        let message = match capabilities.physics {
+            PhysicsSupport::None => Some("This format does not store physics, dropped it"),
+            PhysicsSupport::XPosition => {
+                Some("This format only stores X positions, dropped the rest of the physics")
+            }
+            PhysicsSupport::PositionAndRotation => Some(
+                "This format only stores positions and rotation, dropped the rest of the physics",
+            ),
+            PhysicsSupport::EchoRecords | PhysicsSupport::FembotRecords => {
+                Some("This format only keeps physics read from it, dropped physics")
+            }
+            PhysicsSupport::Full => None,
+        };
+        if let Some(message) = message {
+            report.push_clicks(LossKind::Physics, replay, physics, |count| {
+                format!("{message} from {count} inputs")
+            });
+        }
+
+        if !capabilities.skip_rows {
+            report.push_clicks(
+                LossKind::SkipRows,
+                replay,
+                |click| (inputs(click) == 0) as usize,
+                |count| format!("This format only stores inputs, dropped {count} empty rows"),
+            );
+        }
+
+        report.push_clicks(
+            LossKind::FrameOrigin,
+            replay,
+            |click| {
+                if click.frame < frame_origin {
+                    inputs(click)
+                } else {
+                    0
+                }
+            },
+            |count| {
+                format!(
+                    "This format can't store inputs before frame {frame_origin}, moved {count} inputs onto it"
+                )
+            },
+        );
+
+        report
+    }
+
+    /// Adds losses for FPS changes, the speedhack and a fractional FPS.
+    fn push_fps(&mut self, replay: &Replay, capabilities: Capabilities) {
+        // Without FPS changes, the replay is saved at the highest FPS it reaches
+        let mut fps = replay.tps;
+        if !capabilities.fps_changes && !replay.fps_changes.is_empty() {
+            fps = replay
+                .fps_changes
+                .iter()
+                .map(|change| change.fps)
+                .fold(fps, f64::max);
+
+            self.push(
+                LossKind::FpsChanges,
+                replay.fps_changes.len(),
+                replay.fps_changes.iter().map(|change| change.frame),
+                format!(
+                    "This format does not support FPS changes, re-timed {} FPS changes",
+                    replay.fps_changes.len()
+                ),
+            );
+        }
+
+        if !capabilities.speedhack && replay.has_speedhack() {
+            fps /= f64::from(replay.speedhack);
+
+            self.push(
+                LossKind::Speedhack,
+                1,
+                [],
+                format!(
+                    "This format does not store the speedhack, baked {}x into the FPS",
+                    replay.speedhack
+                ),
+            );
+        }
+
+        if capabilities.integer_fps && fps.fract() != 0.0 {
+            self.push(
+                LossKind::FractionalFps,
+                1,
+                [],
+                format!("This format stores whole FPS values, {fps} FPS will not be kept"),
+            );
+        }
+    }
+
+    /// Adds a warning when a replay recorded on `from` is saved in a format
+    /// made for another version, `to`.
+    pub fn push_game_version(&mut self, from: GameVersion, to: GameVersion, profiled: bool) {
+        if from == GameVersion::Any || to == GameVersion::Any || from == to {
+            return;
+        }
+
+        // Profiles only re-time replays between 2.1 and 2.2
+        let advice = if profiled || from.same_update(to) {
+            ""
+        } else {
+            ", inputs may be off without a conversion profile"
+        };
+
+        self.push(
+            LossKind::GameVersion,
+            1,
+            [],
+            format!("This format is made for {to}, but the replay was recorded on {from}{advice}"),
+        );
+    }
+
+    /// Adds the adjustments made by `profile`, if it changed anything.
+    pub fn push_profile(&mut self, profile: &ConversionProfile, changes: &[String]) {
+        if changes.is_empty() {
+            return;
+        }
+
+        self.push(
+            LossKind::Profile,
+            changes.len(),
+            [],
+            format!(
+                "Applied the {} profile: {}",
+                profile.name(),
+                changes.join(", ")
+            ),
+        );
+    }
+
+    fn push(
+        &mut self,
+        kind: LossKind,
+        count: usize,
+        frames: impl IntoIterator<Item = u32>,
+        message: String,
+    ) {
+        self.losses.push(Loss {
+            kind,
+            count,
+            frames: frames.into_iter().take(EXAMPLE_FRAMES).collect(),
+            message,
+        });
+    }
+
+    /// Adds a loss for the clicks where `lost` counts anything, if there are
+    /// any.
+    fn push_clicks(
+        &mut self,
+        kind: LossKind,
+        replay: &Replay,
+        lost: impl Fn(&Click) -> usize,
+        message: impl FnOnce(usize) -> String,
+    ) {
+        let mut count = 0;
+        let mut frames = vec![];
+
+        for click in &replay.clicks {
+            let lost = lost(click);
+            if lost > 0 {
+                count += lost;
+                frames.push(click.frame);
+            }
+        }
+
+        if count > 0 {
+            self.push(kind, count, frames, message(count));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Loss> {
+        self.losses.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::{Button, ClickType, FpsChange, MetadataField, ReplayMetadata};
+    use crate::test_utils::{replay, with_clicks};
+
+    /// Everything a replay can hold.
+    const FULL: Capabilities = Capabilities {
+        two_player: true,
+        buttons: true,
+        fps_changes: true,
+        metadata: &MetadataField::ALL,
+        physics: PhysicsSupport::Full,
+        integer_fps: false,
+        skip_rows: true,
+        speedhack: true,
+        game_version: true,
+    };
+
+    fn x_position(x: f64) -> Physics {
+        Physics {
+            x,
+            ..Default::default()
+        }
+    }
+
+    /// The only loss of `kind` in the report.
+    fn only(report: &LossReport, kind: LossKind) -> Loss {
+        let losses: Vec<&Loss> = report.iter().filter(|loss| loss.kind == kind).collect();
+        assert_eq!(losses.len(), 1, "expected one {kind:?} loss");
+        losses[0].clone()
+    }
+
+    #[test]
+    fn lossless() {
+        let replay = with_clicks(
+            240.0,
+            vec![Click::from_hold(0, true, false).with_physics(false, Some(x_position(1.0)))],
+        );
+
+        assert!(LossReport::new(&replay, FULL, 0).is_lossless());
+    }
+
+    #[test]
+    fn buttons() {
+        let replay = with_clicks(
+            240.0,
+            vec![
+                Click::from_hold(0, true, false),
+                Click::from_button(5, true, false, Button::Left),
+                Click::from_button(6, false, true, Button::Right),
+            ],
+        );
+        let capabilities = Capabilities {
+            buttons: false,
+            ..FULL
+        };
+
+        let loss = only(
+            &LossReport::new(&replay, capabilities, 0),
+            LossKind::Buttons,
+        );
+        assert_eq!(loss.count, 2);
+        assert_eq!(loss.frames, [5, 6]);
+    }
+
+    #[test]
+    fn fps_changes() {
+        let mut replay = replay(240.0);
+        replay.fps_changes = vec![FpsChange {
+            frame: 10,
+            fps: 360.0,
+        }];
+        let capabilities = Capabilities {
+            fps_changes: false,
+            ..FULL
+        };
+
+        let report = LossReport::new(&replay, capabilities, 0);
+        let loss = only(&report, LossKind::FpsChanges);
+        assert_eq!(loss.count, 1);
+        assert_eq!(loss.frames, [10]);
+    }
+
+    #[test]
+    fn metadata() {
+        let mut replay = replay(240.0);
+        replay.metadata = ReplayMetadata {
+            author: Some("author".to_owned()),
+            seed: Some(1),
+            ..Default::default()
+        };
+        let capabilities = Capabilities {
+            metadata: &[MetadataField::Author],
+            ..FULL
+        };
+
+        let loss = only(
+            &LossReport::new(&replay, capabilities, 0),
+            LossKind::Metadata,
+        );
+        assert_eq!(loss.count, 1);
+        assert_eq!(loss.message, "This format can't store the seed");
+    }
+
+    #[test]
+    fn fractional_fps() {
+        let mut replay = replay(240.0);
+        replay.set_tps(59.94);
+        let capabilities = Capabilities {
+            integer_fps: true,
+            ..FULL
+        };
+
+        only(
+            &LossReport::new(&replay, capabilities, 0),
+            LossKind::FractionalFps,
+        );
+
+        replay.set_tps(60.0);
+        assert!(LossReport::new(&replay, capabilities, 0).is_lossless());
+    }
+
+    #[test]
+    fn physics() {
+        let physics = Physics {
+            y: 105.0,
+            ..x_position(1.0)
+        };
+        let replay = with_clicks(
+            240.0,
+            vec![
+                Click::from_hold(0, true, false).with_physics(false, Some(x_position(1.0))),
+                Click::from_hold(5, false, false).with_physics(false, Some(physics)),
+            ],
+        );
+        let report = |physics| LossReport::new(&replay, Capabilities { physics, ..FULL }, 0);
+
+        let loss = only(&report(PhysicsSupport::None), LossKind::Physics);
+        assert_eq!(loss.count, 2);
+
+        // Physics with only an X position are kept
+        let loss = only(&report(PhysicsSupport::XPosition), LossKind::Physics);
+        assert_eq!(loss.count, 1);
+        assert_eq!(loss.frames, [5]);
+
+        assert!(report(PhysicsSupport::PositionAndRotation).is_lossless());
+    }
+
+    #[test]
+    fn physics_records() {
+        let echo = Physics {
+            raw: Some(RawPhysics::Echo([0; 28])),
+            ..x_position(1.0)
+        };
+        let replay = with_clicks(
+            240.0,
+            vec![Click::from_hold(0, true, false).with_physics(false, Some(echo))],
+        );
+        let report = |physics| LossReport::new(&replay, Capabilities { physics, ..FULL }, 0);
+
+        assert!(report(PhysicsSupport::EchoRecords).is_lossless());
+        only(&report(PhysicsSupport::FembotRecords), LossKind::Physics);
+        only(&report(PhysicsSupport::XPosition), LossKind::Physics);
+        only(
+            &report(PhysicsSupport::PositionAndRotation),
+            LossKind::Physics,
+        );
+    }
+
+    #[test]
+    fn skip_rows() {
+        let mut skip = Click::from_hold(5, true, false);
+        skip.p1 = ClickType::Skip;
+        let replay = with_clicks(240.0, vec![Click::from_hold(0, true, false), skip]);
+        let capabilities = Capabilities {
+            skip_rows: false,
+            ..FULL
+        };
+
+        let loss = only(
+            &LossReport::new(&replay, capabilities, 0),
+            LossKind::SkipRows,
+        );
+        assert_eq!(loss.count, 1);
+        assert_eq!(loss.frames, [5]);
+    }
+
+    #[test]
+    fn speedhack() {
+        let mut replay = replay(240.0);
+        replay.speedhack = 0.5;
+        let capabilities = Capabilities {
+            speedhack: false,
+            ..FULL
+        };
+
+        let loss = only(
+            &LossReport::new(&replay, capabilities, 0),
+            LossKind::Speedhack,
+        );
+        assert_eq!(
+            loss.message,
+            "This format does not store the speedhack, baked 0.5x into the FPS"
+        );
+    }
+
+    #[test]
+    fn frame_origin() {
+        let replay = with_clicks(
+            240.0,
+            vec![
+                Click::from_hold(0, true, false),
+                Click::from_hold(1, false, false),
+                Click::from_hold(2, true, false),
+            ],
+        );
+
+        let loss = only(&LossReport::new(&replay, FULL, 2), LossKind::FrameOrigin);
+        assert_eq!(loss.count, 2);
+        assert_eq!(loss.frames, [0, 1]);
+    }
+
+    #[test]
+    fn game_version() {
+        let mut report = LossReport::default();
+        report.push_game_version(GameVersion::Version2206, GameVersion::Version2206, false);
+        report.push_game_version(GameVersion::Any, GameVersion::Version2113, false);
+        assert!(report.is_lossless());
+
+        report.push_game_version(GameVersion::Version2206, GameVersion::Version2204, false);
+        let loss = only(&report, LossKind::GameVersion);
+        assert!(!loss.message.contains("conversion profile"));
+
+        let mut report = LossReport::default();
+        report.push_game_version(GameVersion::Version2113, GameVersion::Version2206, false);
+        let loss = only(&report, LossKind::GameVersion);
+        assert!(loss.message.contains("conversion profile"));
+    }
+
+    #[test]
+    fn profile() {
+        let profile = ConversionProfile::v2_1_to_v2_2();
+        let mut report = LossReport::default();
+        report.push_profile(&profile, &[]);
+        assert!(report.is_lossless());
+
+        report.push_profile(&profile, &["a change".to_owned(), "another".to_owned()]);
+        let loss = only(&report, LossKind::Profile);
+        assert_eq!(loss.count, 2);
+    }
+
+    #[test]
+    fn skipped_inputs() {
+        let mut replay = replay(240.0);
+        replay.skipped_inputs = vec![3, 8];
+
+        let loss = only(&LossReport::new(&replay, FULL, 0), LossKind::SkippedInputs);
+        assert_eq!(loss.count, 2);
+        assert_eq!(loss.frames, [3, 8]);
+    }
+}
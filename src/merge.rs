@@ -0,0 +1,313 @@
+use std::fmt::Display;
+
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Button, Click, Replay};
+use crate::resample::RoundingStrategy;
+use crate::selection::Players;
+
+/// Why an input didn't make it into a merged replay as it was.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// An input where the other replay takes over, which was dropped.
+    Dropped,
+    /// A button still held where the other replay takes over, which it
+    /// doesn't release first.
+    Held,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct MergeConflict {
+    pub kind: ConflictKind,
+    pub frame: u32,
+    pub player_2: bool,
+    pub button: Button,
+    /// Whether the input comes from the replay merged in, rather than the
+    /// loaded one.
+    pub merged: bool,
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let player = if self.player_2 { "P2" } else { "P1" };
+        let replay = if self.merged { "merged" } else { "loaded" };
+
+        match self.kind {
+            ConflictKind::Dropped => write!(
+                f,
+                "FRAME {}: dropped a {player} input of the {replay} replay",
+                self.frame
+            ),
+            ConflictKind::Held => write!(
+                f,
+                "FRAME {}: {player} is still holding when the merged replay takes over",
+                self.frame
+            ),
+        }
+    }
+}
+
+/// What merging two replays changed.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, Default)]
+pub struct MergeReport {
+    /// TPS of the merged replay before it was resampled to the loaded
+    /// one's, or `None` if they matched.
+    pub resampled_from: Option<f64>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Adds a conflict for every input in `click`.
+fn push_conflicts(conflicts: &mut Vec<MergeConflict>, click: &Click, merged: bool) {
+    let _ = click.apply_button(|frame, _, player_2, button| {
+        conflicts.push(MergeConflict {
+            kind: ConflictKind::Dropped,
+            frame,
+            player_2,
+            button,
+            merged,
+        });
+
+        Ok::<_, ()>(())
+    });
+}
+
+/// The last input (`true` for a press) of each player and button in
+/// `clicks`, indexed by player and then button.
+fn last_inputs<'a>(clicks: impl IntoIterator<Item = &'a Click>) -> [[Option<bool>; 3]; 2] {
+    let mut last = [[None; 3]; 2];
+    for click in clicks {
+        let _ = click.apply_button(|_, hold, player_2, button| {
+            last[player_2 as usize][button as usize - 1] = Some(hold);
+            Ok::<_, ()>(())
+        });
+    }
+
+    last
+}
+
+impl Replay {
+    /// Brings `other` to this replay's timing: both lose their FPS changes,
+    /// and `other` is resampled to this replay's TPS.
+    fn align(&mut self, mut other: Self) -> (Self, Option<f64>) {
+        if !self.fps_changes.is_empty() {
+            *self = self.retimed_without_fps_changes();
+        }
+        if !other.fps_changes.is_empty() {
+            other = other.retimed_without_fps_changes();
+        }
+
+        // Replays without a valid TPS can't be resampled
+        let mut resampled_from = None;
+        if self.tps > 0.0 && other.tps > 0.0 && (self.tps - other.tps).abs() >= f64::EPSILON {
+            resampled_from = Some(other.tps);
+            other.resample(self.tps, RoundingStrategy::default());
+        }
+
+        (other, resampled_from)
+    }
+
+    /// Keeps player 1 of this replay and takes player 2 from `other`. Inputs
+    /// of the other player in either replay are dropped and reported.
+    pub fn merge_players(&mut self, other: Self) -> MergeReport {
+        let (other, resampled_from) = self.align(other);
+        let mut conflicts = vec![];
+
+        let mut p1 = vec![];
+        for click in &self.clicks {
+            let (own, rest) = Players::P1.split(*click);
+            p1.extend(own);
+            if let Some(rest) = rest {
+                push_conflicts(&mut conflicts, &rest, false);
+            }
+        }
+
+        let mut p2 = vec![];
+        for click in &other.clicks {
+            let (own, rest) = Players::P2.split(*click);
+            p2.extend(own);
+            if let Some(rest) = rest {
+                push_conflicts(&mut conflicts, &rest, true);
+            }
+        }
+
+        self.clicks = p1;
+        self.insert_sorted(p2);
+        conflicts.sort_by_key(|conflict| conflict.frame);
+
+        MergeReport {
+            resampled_from,
+            conflicts,
+        }
+    }
+
+    /// Places `other` from `frame` on, so its frame 0 lands on `frame`.
+    /// Inputs of this replay on or after `frame` are dropped and reported,
+    /// as are buttons this replay still holds that `other` doesn't release
+    /// first.
+    ///
+    /// `frame` is a frame of this replay as loaded, and is re-timed along
+    /// with it when it loses its FPS changes.
+    pub fn append(&mut self, other: Self, frame: u32) -> MergeReport {
+        let frame = if self.fps_changes.is_empty() {
+            frame
+        } else {
+            self.retimed_frame(frame)
+        };
+        let (mut other, resampled_from) = self.align(other);
+        let mut conflicts = vec![];
+
+        let (kept, dropped): (Vec<Click>, Vec<Click>) =
+            self.clicks.iter().partition(|click| click.frame < frame);
+        // `other` has to release what's still held before pressing it again
+        let held = last_inputs(&kept);
+        let first = last_inputs(other.clicks.iter().rev());
+        for (player_2, buttons) in [false, true].into_iter().zip(held) {
+            for (button, hold) in [Button::Jump, Button::Left, Button::Right]
+                .into_iter()
+                .zip(buttons)
+            {
+                if hold == Some(true)
+                    && first[player_2 as usize][button as usize - 1] != Some(false)
+                {
+                    conflicts.push(MergeConflict {
+                        kind: ConflictKind::Held,
+                        frame,
+                        player_2,
+                        button,
+                        merged: false,
+                    });
+                }
+            }
+        }
+
+        for click in &dropped {
+            push_conflicts(&mut conflicts, click, false);
+        }
+
+        self.clicks = kept;
+        for click in &mut other.clicks {
+            click.frame = click.frame.saturating_add(frame);
+        }
+        self.clicks.append(&mut other.clicks);
+
+        MergeReport {
+            resampled_from,
+            conflicts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::{ClickType, FpsChange};
+    use crate::test_utils::{frames, with_inputs};
+
+    #[test]
+    fn merge_players_takes_player_2_from_the_other_replay() {
+        let mut loaded = with_inputs(240.0, &[(1, true, false), (2, true, true)]);
+        let other = with_inputs(240.0, &[(3, true, false), (4, true, true)]);
+
+        let report = loaded.merge_players(other);
+
+        assert_eq!(frames(&loaded.clicks), [1, 4]);
+        assert!(loaded.clicks[0].p1.is_click() && loaded.clicks[0].p2.is_skip());
+        assert!(loaded.clicks[1].p1.is_skip() && loaded.clicks[1].p2.is_click());
+        assert_eq!(report.resampled_from, None);
+
+        let dropped: Vec<(u32, bool)> = report
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.frame, conflict.merged))
+            .collect();
+        assert_eq!(dropped, [(2, false), (3, true)]);
+    }
+
+    #[test]
+    fn merge_players_keeps_shared_rows_apart() {
+        let mut loaded = with_inputs(240.0, &[]);
+        loaded.clicks.push(Click {
+            p2: ClickType::Release,
+            ..Click::from_hold(5, true, false)
+        });
+        let other = with_inputs(240.0, &[(5, true, true)]);
+
+        let report = loaded.merge_players(other);
+
+        // Player 1 of the loaded replay first, then player 2 of the other
+        assert_eq!(frames(&loaded.clicks), [5, 5]);
+        assert!(loaded.clicks[0].p1.is_click() && loaded.clicks[0].p2.is_skip());
+        assert!(loaded.clicks[1].p2.is_click());
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].kind, ConflictKind::Dropped);
+    }
+
+    #[test]
+    fn other_replay_is_resampled_to_the_loaded_tps() {
+        let mut loaded = with_inputs(240.0, &[]);
+        let other = with_inputs(60.0, &[(10, true, true)]);
+
+        let report = loaded.merge_players(other);
+
+        assert_eq!(report.resampled_from, Some(60.0));
+        assert_eq!(frames(&loaded.clicks), [40]);
+    }
+
+    #[test]
+    fn append_drops_inputs_from_the_frame_on() {
+        let mut loaded = with_inputs(
+            240.0,
+            &[(5, true, false), (8, false, false), (20, true, false)],
+        );
+        let other = with_inputs(240.0, &[(0, true, false), (3, false, false)]);
+
+        let report = loaded.append(other, 10);
+
+        assert_eq!(frames(&loaded.clicks), [5, 8, 10, 13]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].kind, ConflictKind::Dropped);
+        assert_eq!(report.conflicts[0].frame, 20);
+    }
+
+    #[test]
+    fn append_reports_buttons_still_held() {
+        let append = |other: Replay| {
+            let mut loaded = with_inputs(240.0, &[(5, true, false)]);
+            loaded.append(other, 10).conflicts
+        };
+
+        // The other replay presses again without releasing first
+        let conflicts = append(with_inputs(240.0, &[(2, true, false)]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::Held);
+        assert_eq!(conflicts[0].frame, 10);
+        assert!(!conflicts[0].player_2);
+
+        assert!(append(with_inputs(240.0, &[(0, false, false)])).is_empty());
+    }
+
+    #[test]
+    fn append_frame_is_retimed_with_the_loaded_replay() {
+        // 60 TPS until frame 10, then 120: frame 14 is at 12 / 60 seconds,
+        // which is frame 24 once everything runs at 120 TPS
+        let mut loaded = with_inputs(
+            60.0,
+            &[(5, true, false), (12, false, false), (16, true, false)],
+        );
+        loaded.fps_changes.push(FpsChange {
+            frame: 10,
+            fps: 120.0,
+        });
+        let other = with_inputs(120.0, &[(0, true, true)]);
+
+        let report = loaded.append(other, 14);
+
+        assert!(loaded.fps_changes.is_empty());
+        assert_eq!(frames(&loaded.clicks), [10, 22, 24]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].frame, 26);
+    }
+}
@@ -0,0 +1,248 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Click, GameVersion, Replay};
+use crate::resample::RoundingStrategy;
+
+/// What happens to inputs that land on the very first frame of a replay once
+/// it's shifted, for game versions that don't register inputs on it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FirstFrameClick {
+    /// Leave them on the first frame. Inputs shifted before it are moved onto
+    /// it.
+    #[default]
+    Keep,
+    /// Move the whole replay later, so that none are on it.
+    Delay,
+    /// Remove them, along with inputs shifted before it.
+    Drop,
+}
+
+/// Name of the update a version belongs to.
+fn update(version: GameVersion) -> &'static str {
+    match version {
+        GameVersion::Any => "any version",
+        GameVersion::Version2113 => "2.1",
+        _ => "2.2",
+    }
+}
+
+/// Timing adjustments applied when saving a replay recorded for one game
+/// version in a format of another. Profiles are opt-in, see
+/// [`crate::Converter::enable_profile`].
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConversionProfile {
+    pub from: GameVersion,
+    pub to: GameVersion,
+    /// TPS to resample the replay to, or `None` to keep its own.
+    pub tps: Option<f64>,
+    pub rounding: RoundingStrategy,
+    /// Frames every input is moved by after resampling, to line up the
+    /// frame origins of both versions.
+    pub frame_shift: i32,
+    /// Applied to the inputs on the first frame after `frame_shift`.
+    pub first_frame: FirstFrameClick,
+}
+
+#[wasm_bindgen]
+impl ConversionProfile {
+    /// 2.1 replays saved for 2.2: resampled to the default of 240 TPS.
+    ///
+    /// Whether 2.1 and 2.2 start counting frames at the same point, and
+    /// whether either registers inputs on the first frame, isn't known, so
+    /// inputs aren't shifted and the first frame is kept.
+    #[must_use]
+    pub fn v2_1_to_v2_2() -> Self {
+        Self {
+            from: GameVersion::Version2113,
+            to: GameVersion::Version2206,
+            tps: Some(240.0),
+            rounding: RoundingStrategy::Nearest,
+            frame_shift: 0,
+            first_frame: FirstFrameClick::Keep,
+        }
+    }
+
+    /// 2.2 replays saved for 2.1, keeping the TPS as the FPS. Like
+    /// [`Self::v2_1_to_v2_2`], inputs aren't shifted.
+    #[must_use]
+    pub fn v2_2_to_v2_1() -> Self {
+        Self {
+            from: GameVersion::Version2206,
+            to: GameVersion::Version2113,
+            tps: None,
+            rounding: RoundingStrategy::Nearest,
+            frame_shift: 0,
+            first_frame: FirstFrameClick::Keep,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        format!("{} to {}", update(self.from), update(self.to))
+    }
+}
+
+impl ConversionProfile {
+    /// Both built-in profiles.
+    #[must_use]
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::v2_1_to_v2_2(), Self::v2_2_to_v2_1()]
+    }
+
+    /// Whether the profile is meant for saving a `from` replay as `to`. Any
+    /// version of the same update matches.
+    #[must_use]
+    pub fn applies(&self, from: GameVersion, to: GameVersion) -> bool {
+        self.from.same_update(from) && self.to.same_update(to)
+    }
+
+    /// Adjusts the replay and marks it as recorded for the target version.
+    /// Returns a description of every adjustment that changed something.
+    pub fn apply(&self, replay: &mut Replay) -> Vec<String> {
+        let mut changes = vec![];
+
+        if let Some(tps) = self.tps {
+            // Replays without a valid TPS can't be resampled
+            if replay.tps > 0.0 && (tps - replay.tps).abs() >= f64::EPSILON {
+                changes.push(format!("resampled from {} to {tps} TPS", replay.tps));
+                replay.resample(tps, self.rounding);
+            }
+        }
+
+        let mut shift = i64::from(self.frame_shift);
+        if shift != 0 {
+            changes.push(format!("shifted inputs by {shift} frame(s)"));
+        }
+
+        // Inputs that land on the first frame, or before it, once shifted
+        let on_first_frame = |click: &Click| i64::from(click.frame) + shift <= 0;
+        match self.first_frame {
+            FirstFrameClick::Keep => {
+                let clamped = replay
+                    .clicks
+                    .iter()
+                    .filter(|click| i64::from(click.frame) + shift < 0)
+                    .count();
+                if clamped > 0 {
+                    changes.push(format!("moved {clamped} inputs onto the first frame"));
+                }
+            }
+            FirstFrameClick::Delay => {
+                // The whole replay moves, so inputs keep their spacing
+                let first = replay
+                    .clicks
+                    .iter()
+                    .map(|click| i64::from(click.frame))
+                    .min();
+                if let Some(first) = first.filter(|first| first + shift <= 0) {
+                    let delay = 1 - (first + shift);
+                    changes.push(format!(
+                        "delayed inputs by {delay} frame(s) to keep them off the first frame"
+                    ));
+                    shift += delay;
+                }
+            }
+            FirstFrameClick::Drop => {
+                let dropped = replay.clicks.iter().filter(|c| on_first_frame(c)).count();
+                if dropped > 0 {
+                    changes.push(format!("dropped {dropped} first-frame inputs"));
+                    replay.clicks.retain(|click| !on_first_frame(click));
+                }
+            }
+        }
+
+        if shift != 0 {
+            replay.shift_frames(shift);
+        }
+
+        if !replay.game_version.same_update(self.to) {
+            replay.game_version = self.to;
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, presses};
+
+    fn profile(frame_shift: i32, first_frame: FirstFrameClick) -> ConversionProfile {
+        ConversionProfile {
+            frame_shift,
+            first_frame,
+            ..ConversionProfile::v2_2_to_v2_1()
+        }
+    }
+
+    /// Frames of a replay on `frames_before` after applying `profile`, and
+    /// its changes.
+    fn apply(profile: ConversionProfile, frames_before: &[u32]) -> (Vec<u32>, Vec<String>) {
+        let mut replay = presses(240.0, frames_before);
+        let changes = profile.apply(&mut replay);
+        (frames(&replay.clicks), changes)
+    }
+
+    #[test]
+    fn builtin_profiles_keep_frames() {
+        for profile in ConversionProfile::builtin() {
+            let (frames, changes) = apply(profile, &[0, 4]);
+            assert_eq!(frames, [0, 4]);
+            assert!(changes.is_empty());
+        }
+    }
+
+    #[test]
+    fn keep_moves_inputs_shifted_before_the_first_frame_onto_it() {
+        let (frames, changes) = apply(profile(-1, FirstFrameClick::Keep), &[0, 4]);
+
+        assert_eq!(frames, [0, 3]);
+        assert!(changes.contains(&"moved 1 inputs onto the first frame".to_string()));
+    }
+
+    #[test]
+    fn delay_moves_every_input_alike() {
+        let delay = |shift, before: &[u32]| apply(profile(shift, FirstFrameClick::Delay), before);
+
+        assert_eq!(delay(0, &[0, 4]).0, [1, 5]);
+        assert_eq!(delay(-1, &[0, 1, 4]).0, [1, 2, 5]);
+
+        // Already off the first frame
+        let (frames, changes) = delay(1, &[0, 4]);
+        assert_eq!(frames, [1, 5]);
+        assert_eq!(changes, ["shifted inputs by 1 frame(s)"]);
+
+        let (frames, changes) = delay(0, &[3, 4]);
+        assert_eq!(frames, [3, 4]);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn drop_removes_inputs_on_the_first_frame() {
+        let (frames, changes) = apply(profile(0, FirstFrameClick::Drop), &[0, 4]);
+        assert_eq!(frames, [4]);
+        assert_eq!(changes, ["dropped 1 first-frame inputs"]);
+
+        // Including the ones shifted before it
+        let (frames, _) = apply(profile(-1, FirstFrameClick::Drop), &[0, 1, 4]);
+        assert_eq!(frames, [3]);
+    }
+
+    #[test]
+    fn resamples_to_the_profile_tps() {
+        let mut replay = presses(120.0, &[0, 5]);
+        let changes = ConversionProfile::v2_1_to_v2_2().apply(&mut replay);
+
+        assert!((replay.tps - 240.0).abs() < f64::EPSILON);
+        assert_eq!(frames(&replay.clicks), [0, 10]);
+        assert_eq!(changes, ["resampled from 120 to 240 TPS"]);
+
+        // Nothing to do at the same TPS
+        assert!(ConversionProfile::v2_1_to_v2_2()
+            .apply(&mut replay)
+            .is_empty());
+    }
+}
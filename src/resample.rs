@@ -0,0 +1,413 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Button, Click, Physics, Replay};
+
+// Keeps frames that are exact in theory (like 150 * 1.5) from being floored
+// or ceiled to the wrong side by float error
+const EPSILON: f64 = 1e-9;
+
+/// How inputs that fall between two frames of the new FPS are placed.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoundingStrategy {
+    /// The frame closest to when the input happened.
+    #[default]
+    Nearest,
+    /// The last frame at or before when the input happened.
+    Floor,
+    /// The first frame at or after when the input happened.
+    Ceil,
+    /// Presses go to the nearest frame, and releases keep the hold length
+    /// of their press, rounded to the nearest frame.
+    PreserveHoldLength,
+}
+
+impl RoundingStrategy {
+    fn round(self, frame: f64) -> f64 {
+        match self {
+            Self::Nearest | Self::PreserveHoldLength => frame.round(),
+            Self::Floor => (frame + EPSILON).floor(),
+            Self::Ceil => (frame - EPSILON).ceil(),
+        }
+    }
+}
+
+/// What to keep when changing the speedhack of a replay.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpeedhackMode {
+    /// Keep the FPS and move every input to another frame.
+    #[default]
+    KeepFps,
+    /// Keep every input on its frame and change the FPS instead.
+    KeepFrames,
+}
+
+/// How much resampling moved inputs away from when they originally happened.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResampleReport {
+    /// Inputs placed more than half a frame away from their original time.
+    pub moved: usize,
+    /// Inputs pushed to a later frame so they don't land on the same frame
+    /// as the previous press or release of the same player and button.
+    pub collapsed: usize,
+    /// Largest distance between an input and its original time, in frames
+    /// of the new FPS.
+    pub max_error: f64,
+}
+
+impl ResampleReport {
+    fn record(&mut self, frame: u32, exact: f64) {
+        let error = (f64::from(frame) - exact).abs();
+
+        if error > 0.5 + EPSILON {
+            self.moved += 1;
+        }
+        self.max_error = self.max_error.max(error);
+    }
+}
+
+/// A single press or release, or a row without any.
+struct Input {
+    frame: u32,
+    kind: InputKind,
+}
+
+enum InputKind {
+    Hold {
+        hold: bool,
+        player_2: bool,
+        button: Button,
+        physics: Option<Physics>,
+    },
+    /// A row where neither player does anything, kept as is.
+    Empty(Click),
+}
+
+/// Presses and releases of one player on one button, which have to stay in
+/// order on separate frames.
+struct Track {
+    player_2: bool,
+    button: Button,
+    last_frame: Option<u32>,
+    /// Original and new frame of the current press.
+    press: Option<(u32, u32)>,
+}
+
+impl Replay {
+    /// Re-times every input to `tps`, keeping when it happens in seconds.
+    /// FPS changes are scaled by the same ratio, so every part of the replay
+    /// is resampled the same way.
+    ///
+    /// Presses and releases that would end up on the same frame as the
+    /// previous input of the same player and button are pushed to the next
+    /// free frame, so holds never disappear.
+    pub fn resample(&mut self, tps: f64, strategy: RoundingStrategy) -> ResampleReport {
+        let ratio = tps / self.tps;
+        let report = self.scale_frames(ratio, strategy);
+
+        for change in &mut self.fps_changes {
+            change.fps *= ratio;
+        }
+        self.change_tps(tps);
+
+        report
+    }
+
+    /// Re-times the replay as if it was recorded at another game speed, so
+    /// every input still happens at the same moment of game time.
+    pub fn change_speedhack(
+        &mut self,
+        speedhack: f32,
+        mode: SpeedhackMode,
+        strategy: RoundingStrategy,
+    ) -> ResampleReport {
+        // A frame lasts `speedhack / tps` seconds of game time
+        let ratio = f64::from(self.speedhack) / f64::from(speedhack);
+
+        let report = match mode {
+            SpeedhackMode::KeepFps => self.scale_frames(ratio, strategy),
+            SpeedhackMode::KeepFrames => {
+                self.change_tps(self.tps / ratio);
+                for change in &mut self.fps_changes {
+                    change.fps /= ratio;
+                }
+
+                ResampleReport::default()
+            }
+        };
+        self.speedhack = speedhack;
+
+        report
+    }
+
+    /// Multiplies the frame of every input and FPS change by `ratio`.
+    fn scale_frames(&mut self, ratio: f64, strategy: RoundingStrategy) -> ResampleReport {
+        let mut report = ResampleReport::default();
+
+        let mut inputs = vec![];
+        for click in &self.clicks {
+            if click.p1.is_skip() && click.p2.is_skip() {
+                inputs.push(Input {
+                    frame: click.frame,
+                    kind: InputKind::Empty(*click),
+                });
+                continue;
+            }
+
+            for (player_2, click_type) in [(false, click.p1), (true, click.p2)] {
+                if !click_type.is_skip() {
+                    inputs.push(Input {
+                        frame: click.frame,
+                        kind: InputKind::Hold {
+                            hold: click_type.is_click(),
+                            player_2,
+                            button: click.button,
+                            physics: click.physics(player_2),
+                        },
+                    });
+                }
+            }
+        }
+        inputs.sort_by_key(|input| input.frame);
+
+        let mut tracks: Vec<Track> = vec![];
+        for input in &mut inputs {
+            let exact = f64::from(input.frame) * ratio;
+
+            let InputKind::Hold {
+                hold,
+                player_2,
+                button,
+                ..
+            } = input.kind
+            else {
+                input.frame = strategy.round(exact).max(0.0) as u32;
+                report.record(input.frame, exact);
+                continue;
+            };
+
+            let idx = tracks
+                .iter()
+                .position(|track| track.player_2 == player_2 && track.button == button)
+                .unwrap_or_else(|| {
+                    tracks.push(Track {
+                        player_2,
+                        button,
+                        last_frame: None,
+                        press: None,
+                    });
+                    tracks.len() - 1
+                });
+            let track = &mut tracks[idx];
+
+            let mut frame = match (strategy, track.press) {
+                (RoundingStrategy::PreserveHoldLength, Some((press, new_press))) if !hold => {
+                    let length = f64::from(input.frame.saturating_sub(press)) * ratio;
+                    f64::from(new_press) + length.round()
+                }
+                _ => strategy.round(exact),
+            }
+            .max(0.0) as u32;
+
+            if let Some(last_frame) = track.last_frame {
+                if frame <= last_frame {
+                    frame = last_frame + 1;
+                    report.collapsed += 1;
+                }
+            }
+
+            track.last_frame = Some(frame);
+            track.press = hold.then_some((input.frame, frame));
+            input.frame = frame;
+            report.record(frame, exact);
+        }
+        inputs.sort_by_key(|input| input.frame);
+
+        self.clicks = merge_inputs(inputs);
+
+        for change in &mut self.fps_changes {
+            change.frame = (f64::from(change.frame) * ratio).round() as u32;
+        }
+
+        report
+    }
+}
+
+/// Puts inputs back into clicks, sharing a click between both players when
+/// they press the same button on the same frame.
+fn merge_inputs(inputs: Vec<Input>) -> Vec<Click> {
+    let mut clicks: Vec<Click> = Vec::with_capacity(inputs.len());
+
+    for input in inputs {
+        let (hold, player_2, button, physics) = match input.kind {
+            InputKind::Empty(click) => {
+                clicks.push(Click {
+                    frame: input.frame,
+                    ..click
+                });
+                continue;
+            }
+            InputKind::Hold {
+                hold,
+                player_2,
+                button,
+                physics,
+            } => (hold, player_2, button, physics),
+        };
+
+        if let Some(last) = clicks.last_mut() {
+            let has_input = !last.p1.is_skip() || !last.p2.is_skip();
+            let free = if player_2 {
+                last.p2.is_skip()
+            } else {
+                last.p1.is_skip()
+            };
+
+            if has_input && free && last.frame == input.frame && last.button == button {
+                if player_2 {
+                    last.p2 = hold.into();
+                    last.p2_physics = physics;
+                } else {
+                    last.p1 = hold.into();
+                    last.p1_physics = physics;
+                }
+                continue;
+            }
+        }
+
+        clicks.push(
+            Click::from_button(input.frame, hold, player_2, button).with_physics(player_2, physics),
+        );
+    }
+
+    clicks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::replay::{ClickType, FpsChange};
+    use crate::test_utils::{frames, with_inputs};
+
+    #[test]
+    fn scales_frames_to_the_new_tps() {
+        let mut replay = with_inputs(60.0, &[(10, true, false), (15, false, false)]);
+        let report = replay.resample(240.0, RoundingStrategy::Nearest);
+
+        assert_eq!(frames(&replay.clicks), [40, 60]);
+        assert!((replay.tps - 240.0).abs() < f64::EPSILON);
+        assert!((replay.fps - 240.0).abs() < f64::EPSILON);
+        assert_eq!(report, ResampleReport::default());
+    }
+
+    #[test]
+    fn rounds_between_frames_by_strategy() {
+        // Frame 6 at 240 TPS is frame 1.5 at 60
+        let resampled = |strategy| {
+            let mut replay = with_inputs(240.0, &[(6, true, false)]);
+            replay.resample(60.0, strategy);
+            frames(&replay.clicks)[0]
+        };
+
+        assert_eq!(resampled(RoundingStrategy::Nearest), 2);
+        assert_eq!(resampled(RoundingStrategy::Floor), 1);
+        assert_eq!(resampled(RoundingStrategy::Ceil), 2);
+    }
+
+    #[test]
+    fn float_error_does_not_cross_frames() {
+        // 15 * 1000 / 60 is 250.00000000000003
+        let mut replay = with_inputs(60.0, &[(15, true, false)]);
+        replay.resample(1000.0, RoundingStrategy::Ceil);
+        assert_eq!(frames(&replay.clicks), [250]);
+
+        // 55 * 360 / 165 is 119.99999999999999
+        let mut replay = with_inputs(165.0, &[(55, true, false)]);
+        replay.resample(360.0, RoundingStrategy::Floor);
+        assert_eq!(frames(&replay.clicks), [120]);
+    }
+
+    #[test]
+    fn colliding_inputs_move_to_the_next_frame() {
+        // Both land on frame 1 at 60 TPS
+        let mut replay = with_inputs(240.0, &[(4, true, false), (5, false, false)]);
+        let report = replay.resample(60.0, RoundingStrategy::Nearest);
+
+        assert_eq!(frames(&replay.clicks), [1, 2]);
+        assert!(replay.clicks[0].p1.is_click());
+        assert!(replay.clicks[1].p1.is_release());
+        assert_eq!(report.collapsed, 1);
+        assert_eq!(report.moved, 1);
+        assert!((report.max_error - 0.75).abs() < EPSILON);
+    }
+
+    #[test]
+    fn collisions_are_per_player() {
+        let mut replay = with_inputs(240.0, &[(4, true, false), (5, true, true)]);
+        let report = replay.resample(60.0, RoundingStrategy::Nearest);
+
+        // Both presses share a click on frame 1
+        assert_eq!(frames(&replay.clicks), [1]);
+        assert!(replay.clicks[0].p1.is_click());
+        assert!(replay.clicks[0].p2.is_click());
+        assert_eq!(report.collapsed, 0);
+    }
+
+    #[test]
+    fn preserve_hold_length_rounds_the_hold() {
+        // Press on 2.4 and release on 4.8: the hold lasts 2.4 frames
+        let inputs = [(1, true, false), (2, false, false)];
+
+        let mut nearest = with_inputs(60.0, &inputs);
+        nearest.resample(144.0, RoundingStrategy::Nearest);
+        assert_eq!(frames(&nearest.clicks), [2, 5]);
+
+        let mut preserved = with_inputs(60.0, &inputs);
+        preserved.resample(144.0, RoundingStrategy::PreserveHoldLength);
+        assert_eq!(frames(&preserved.clicks), [2, 4]);
+    }
+
+    #[test]
+    fn empty_rows_and_fps_changes_are_scaled() {
+        let mut replay = with_inputs(60.0, &[(3, true, false)]);
+        replay.clicks.push(Click {
+            p1: ClickType::Skip,
+            ..Click::from_hold(5, false, false)
+        });
+        replay.fps_changes.push(FpsChange {
+            frame: 4,
+            fps: 120.0,
+        });
+
+        replay.resample(120.0, RoundingStrategy::Nearest);
+
+        assert_eq!(frames(&replay.clicks), [6, 10]);
+        assert!(replay.clicks[1].p1.is_skip() && replay.clicks[1].p2.is_skip());
+        assert_eq!(
+            replay.fps_changes,
+            [FpsChange {
+                frame: 8,
+                fps: 240.0
+            }]
+        );
+    }
+
+    #[test]
+    fn speedhack_keeps_game_time() {
+        let inputs = [(10, true, false), (20, false, false)];
+
+        // Half speed: a frame lasts half as much game time
+        let mut keep_fps = with_inputs(60.0, &inputs);
+        keep_fps.change_speedhack(0.5, SpeedhackMode::KeepFps, RoundingStrategy::Nearest);
+        assert_eq!(frames(&keep_fps.clicks), [20, 40]);
+        assert!((keep_fps.tps - 60.0).abs() < f64::EPSILON);
+
+        let mut keep_frames = with_inputs(60.0, &inputs);
+        keep_frames.change_speedhack(0.5, SpeedhackMode::KeepFrames, RoundingStrategy::Nearest);
+        assert_eq!(frames(&keep_frames.clicks), [10, 20]);
+        assert!((keep_frames.tps - 30.0).abs() < f64::EPSILON);
+        assert!((keep_frames.speedhack - 0.5).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,306 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formats::replay::{Click, ClickType, Replay};
+
+/// Which players' inputs an operation touches.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Players {
+    #[default]
+    Both,
+    P1,
+    P2,
+}
+
+impl Players {
+    /// Splits a click into the part belonging to these players and the part
+    /// that doesn't. Parts without an input are `None`, except that a row
+    /// without any input belongs to [`Players::Both`] as a whole.
+    #[must_use]
+    pub fn split(self, click: Click) -> (Option<Click>, Option<Click>) {
+        let player_2 = match self {
+            Self::Both => return (Some(click), None),
+            Self::P1 => false,
+            Self::P2 => true,
+        };
+
+        let (own, other) = if player_2 {
+            (click.p2, click.p1)
+        } else {
+            (click.p1, click.p2)
+        };
+        if own.is_skip() {
+            return (None, Some(click));
+        }
+
+        let part = |player_2: bool| {
+            let mut part = click;
+            if player_2 {
+                part.p1 = ClickType::Skip;
+                part.p1_physics = None;
+            } else {
+                part.p2 = ClickType::Skip;
+                part.p2_physics = None;
+            }
+            part
+        };
+
+        let rest = (!other.is_skip()).then(|| part(!player_2));
+        (Some(part(player_2)), rest)
+    }
+}
+
+/// A range of inputs, either by frame or by position in the replay. Both
+/// ends are included.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub first: u32,
+    pub last: u32,
+    by_index: bool,
+    pub players: Players,
+}
+
+#[wasm_bindgen]
+impl Selection {
+    /// Every input from frame `first` to frame `last`.
+    #[must_use]
+    pub fn frames(first: u32, last: u32) -> Self {
+        Self {
+            first,
+            last,
+            by_index: false,
+            players: Players::Both,
+        }
+    }
+
+    /// Every input from position `first` to position `last`.
+    #[must_use]
+    pub fn indices(first: u32, last: u32) -> Self {
+        Self {
+            first,
+            last,
+            by_index: true,
+            players: Players::Both,
+        }
+    }
+
+    /// Only the inputs of `players` in the range.
+    #[must_use]
+    pub fn only(mut self, players: Players) -> Self {
+        self.players = players;
+        self
+    }
+
+    #[must_use]
+    pub fn is_by_index(&self) -> bool {
+        self.by_index
+    }
+}
+
+impl Selection {
+    fn contains(&self, index: usize, click: &Click) -> bool {
+        let position = if self.by_index {
+            index as u32
+        } else {
+            click.frame
+        };
+
+        (self.first..=self.last).contains(&position)
+    }
+}
+
+/// Counts the frames of `clicks` from the start of `selection`.
+fn relative(mut clicks: Vec<Click>, selection: Selection) -> Vec<Click> {
+    let start = if selection.by_index {
+        clicks.first().map_or(0, |click| click.frame)
+    } else {
+        selection.first
+    };
+
+    for click in &mut clicks {
+        click.frame = click.frame.saturating_sub(start);
+    }
+
+    clicks
+}
+
+impl Replay {
+    /// The selected inputs, with frames counted from the start of the
+    /// selection: its first frame, or the frame of its first input when
+    /// selecting by position.
+    #[must_use]
+    pub fn copy_range(&self, selection: Selection) -> Vec<Click> {
+        let clicks = self
+            .clicks
+            .iter()
+            .enumerate()
+            .filter(|(index, click)| selection.contains(*index, click))
+            .filter_map(|(_, click)| selection.players.split(*click).0)
+            .collect();
+
+        relative(clicks, selection)
+    }
+
+    /// Removes the selected inputs and returns them like
+    /// [`Replay::copy_range`].
+    pub fn take_range(&mut self, selection: Selection) -> Vec<Click> {
+        let taken = self.remove_range(selection);
+        relative(taken, selection)
+    }
+
+    /// Moves the selected inputs by `offset` frames, stopping at frame 0.
+    pub fn shift_range(&mut self, selection: Selection, offset: i64) {
+        let mut clicks = self.remove_range(selection);
+        for click in &mut clicks {
+            click.frame = (i64::from(click.frame) + offset).max(0) as u32;
+        }
+
+        self.insert_sorted(clicks);
+    }
+
+    /// Inserts `clicks`, counted from `frame`, keeping only the inputs of
+    /// `players`.
+    pub fn paste(&mut self, clicks: &[Click], frame: u32, players: Players) {
+        let clicks = clicks
+            .iter()
+            .filter_map(|click| players.split(*click).0)
+            .map(|mut click| {
+                click.frame = click.frame.saturating_add(frame);
+                click
+            })
+            .collect();
+
+        self.insert_sorted(clicks);
+    }
+
+    /// Removes the selected inputs and returns them on their own frames.
+    fn remove_range(&mut self, selection: Selection) -> Vec<Click> {
+        let mut removed = vec![];
+        let mut kept = Vec::with_capacity(self.clicks.len());
+
+        for (index, click) in self.clicks.iter().enumerate() {
+            if !selection.contains(index, click) {
+                kept.push(*click);
+                continue;
+            }
+
+            let (part, rest) = selection.players.split(*click);
+            removed.extend(part);
+            kept.extend(rest);
+        }
+
+        self.clicks = kept;
+        removed
+    }
+
+    /// Adds `clicks` after the inputs already on their frames, like a stable
+    /// sort would, so a sorted replay stays sorted.
+    pub(crate) fn insert_sorted(&mut self, mut clicks: Vec<Click>) {
+        clicks.sort_by_key(|click| click.frame);
+
+        let mut merged = Vec::with_capacity(self.clicks.len() + clicks.len());
+        let mut clicks = clicks.into_iter().peekable();
+        for click in self.clicks.drain(..) {
+            while let Some(inserted) = clicks.next_if(|inserted| inserted.frame < click.frame) {
+                merged.push(inserted);
+            }
+            merged.push(click);
+        }
+        merged.extend(clicks);
+
+        self.clicks = merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, with_clicks};
+
+    /// A press of both players on `frame`.
+    fn both(frame: u32) -> Click {
+        Click {
+            p2: ClickType::Click,
+            ..Click::from_hold(frame, true, false)
+        }
+    }
+
+    #[test]
+    fn split_separates_players() {
+        let (p1, rest) = Players::P1.split(both(3));
+        let (p1, rest) = (p1.unwrap(), rest.unwrap());
+        assert!(p1.p1.is_click() && p1.p2.is_skip());
+        assert!(rest.p1.is_skip() && rest.p2.is_click());
+
+        let (own, rest) = Players::P2.split(Click::from_hold(3, true, false));
+        assert!(own.is_none());
+        assert!(rest.is_some());
+
+        let (own, rest) = Players::Both.split(both(3));
+        assert!(own.is_some() && rest.is_none());
+    }
+
+    #[test]
+    fn copies_count_from_the_start_of_the_selection() {
+        let replay = with_clicks(
+            240.0,
+            vec![
+                Click::from_hold(5, true, false),
+                Click::from_hold(10, false, false),
+                Click::from_hold(20, true, false),
+            ],
+        );
+
+        assert_eq!(frames(&replay.copy_range(Selection::frames(4, 10))), [1, 6]);
+        assert_eq!(
+            frames(&replay.copy_range(Selection::indices(1, 2))),
+            [0, 10]
+        );
+    }
+
+    #[test]
+    fn taking_one_player_keeps_the_other() {
+        let mut replay = with_clicks(240.0, vec![both(5), Click::from_hold(8, false, false)]);
+
+        let taken = replay.take_range(Selection::frames(0, 10).only(Players::P1));
+
+        assert_eq!(frames(&taken), [5, 8]);
+        assert_eq!(frames(&replay.clicks), [5]);
+        assert!(replay.clicks[0].p1.is_skip() && replay.clicks[0].p2.is_click());
+    }
+
+    #[test]
+    fn shifting_stops_at_frame_0_and_stays_sorted() {
+        let mut replay = with_clicks(
+            240.0,
+            vec![
+                Click::from_hold(2, true, false),
+                Click::from_hold(6, true, true),
+                Click::from_hold(10, false, false),
+            ],
+        );
+
+        replay.shift_range(Selection::frames(0, 2), 6);
+        assert_eq!(frames(&replay.clicks), [6, 8, 10]);
+        // Shifted inputs go after the ones already on the frame
+        assert!(replay.clicks[0].p2.is_click());
+
+        replay.shift_range(Selection::indices(1, 2), -20);
+        assert_eq!(frames(&replay.clicks), [0, 0, 6]);
+    }
+
+    #[test]
+    fn paste_keeps_only_the_chosen_players() {
+        let mut replay = with_clicks(240.0, vec![Click::from_hold(0, true, false)]);
+
+        replay.paste(
+            &[both(0), Click::from_hold(4, false, false)],
+            10,
+            Players::P2,
+        );
+
+        assert_eq!(frames(&replay.clicks), [0, 10]);
+        assert!(replay.clicks[1].p1.is_skip() && replay.clicks[1].p2.is_click());
+    }
+}
@@ -0,0 +1,43 @@
+//! Replays shared by the unit tests of every module.
+
+use crate::formats::replay::{Click, GameVersion, Replay};
+use crate::Settings;
+
+/// An empty replay at `tps`, for any game version.
+pub fn replay(tps: f64) -> Replay {
+    Replay::new(tps, GameVersion::Any, Settings::default())
+}
+
+/// A replay at `tps` with `clicks`.
+pub fn with_clicks(tps: f64, clicks: Vec<Click>) -> Replay {
+    let mut replay = replay(tps);
+    replay.clicks = clicks;
+    replay
+}
+
+/// A replay at `tps` with a jump input on every `(frame, hold, player_2)`.
+pub fn with_inputs(tps: f64, inputs: &[(u32, bool, bool)]) -> Replay {
+    with_clicks(
+        tps,
+        inputs
+            .iter()
+            .map(|&(frame, hold, player_2)| Click::from_hold(frame, hold, player_2))
+            .collect(),
+    )
+}
+
+/// A replay at `tps` with a press of player 1 on every frame of `frames`.
+pub fn presses(tps: f64, frames: &[u32]) -> Replay {
+    with_clicks(
+        tps,
+        frames
+            .iter()
+            .map(|&frame| Click::from_hold(frame, true, false))
+            .collect(),
+    )
+}
+
+/// Frames of every click.
+pub fn frames<'a>(clicks: impl IntoIterator<Item = &'a Click>) -> Vec<u32> {
+    clicks.into_iter().map(|click| click.frame).collect()
+}
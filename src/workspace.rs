@@ -0,0 +1,235 @@
+use std::mem;
+
+use crate::formats::replay::Replay;
+use crate::history::History;
+
+/// Name of the slot a converter starts with.
+pub const DEFAULT_SLOT: &str = "main";
+
+/// A named replay, with its own undo history.
+#[derive(Clone, Default)]
+struct Slot {
+    name: String,
+    replay: Replay,
+    history: History,
+}
+
+/// Named replays loaded side by side. The active replay and its history
+/// live in the converter, so every editing method works on them, and its
+/// slot here stays empty until another one is activated.
+#[derive(Clone)]
+pub struct Workspace {
+    slots: Vec<Slot>,
+    active: usize,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self {
+            slots: vec![Slot {
+                name: DEFAULT_SLOT.to_string(),
+                ..Default::default()
+            }],
+            active: 0,
+        }
+    }
+}
+
+impl Workspace {
+    /// Names of every slot, in the order they were added.
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.slots.iter().map(|slot| slot.name.clone()).collect()
+    }
+
+    #[must_use]
+    pub fn active(&self) -> &str {
+        &self.slots[self.active].name
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.name == name)
+    }
+
+    /// Adds an empty slot. Returns `false` if the name is taken.
+    pub fn add(&mut self, name: &str) -> bool {
+        if self.contains(name) {
+            return false;
+        }
+
+        self.slots.push(Slot {
+            name: name.to_string(),
+            ..Default::default()
+        });
+
+        true
+    }
+
+    /// Removes an inactive slot. Returns `false` if there is no such slot or
+    /// it is the active one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(index) if index != self.active => {
+                self.slots.remove(index);
+                if index < self.active {
+                    self.active -= 1;
+                }
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Makes `name` the active slot. `replay` and `history` are the active
+    /// ones, which are stored in their slot and replaced by the new slot's.
+    /// Returns `false` if there is no such slot.
+    pub fn activate(&mut self, name: &str, replay: &mut Replay, history: &mut History) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+
+        let current = &mut self.slots[self.active];
+        current.replay = mem::take(replay);
+        current.history = mem::take(history);
+
+        let next = &mut self.slots[index];
+        *replay = mem::take(&mut next.replay);
+        *history = mem::take(&mut next.history);
+        self.active = index;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, presses};
+    use crate::{Converter, Format};
+
+    const REPLAY: &str = r#"{"format":"obot-converter","version":2,"tps":60.0,"game_version":"any","clicks":[{"frame":3,"p1":"click"}]}"#;
+
+    fn workspace(names: &[&str]) -> Workspace {
+        let mut workspace = Workspace::default();
+        for name in names {
+            assert!(workspace.add(name));
+        }
+        workspace
+    }
+
+    #[test]
+    fn only_inactive_slots_hold_their_replay() {
+        let mut workspace = workspace(&["other"]);
+        let mut replay = presses(240.0, &[1, 2]);
+        let mut history = History::default();
+
+        assert!(workspace.activate("other", &mut replay, &mut history));
+        assert_eq!(workspace.active(), "other");
+        assert!(replay.clicks.is_empty());
+        assert_eq!(frames(&workspace.slots[0].replay.clicks), [1, 2]);
+        assert!(workspace.slots[1].replay.clicks.is_empty());
+
+        assert!(workspace.activate(DEFAULT_SLOT, &mut replay, &mut history));
+        assert_eq!(frames(&replay.clicks), [1, 2]);
+        assert!(workspace.slots[0].replay.clicks.is_empty());
+    }
+
+    #[test]
+    fn activating_an_unknown_slot_keeps_the_active_one() {
+        let mut workspace = workspace(&[]);
+        let mut replay = presses(240.0, &[1]);
+        let mut history = History::default();
+
+        assert!(!workspace.activate("missing", &mut replay, &mut history));
+        assert_eq!(workspace.active(), DEFAULT_SLOT);
+        assert_eq!(frames(&replay.clicks), [1]);
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut workspace = workspace(&["other"]);
+
+        assert!(!workspace.add("other"));
+        assert!(!workspace.add(DEFAULT_SLOT));
+        assert_eq!(workspace.names(), [DEFAULT_SLOT, "other"]);
+    }
+
+    #[test]
+    fn removing_a_slot_before_the_active_one_keeps_it_active() {
+        let mut workspace = workspace(&["a", "b"]);
+        let (mut replay, mut history) = (presses(240.0, &[5]), History::default());
+        workspace.activate("b", &mut replay, &mut history);
+
+        assert!(!workspace.remove("b"));
+        assert!(!workspace.remove("missing"));
+        assert!(workspace.remove("a"));
+        assert_eq!(workspace.active(), "b");
+        assert_eq!(workspace.names(), [DEFAULT_SLOT, "b"]);
+
+        // The replay stored before the active slot is still found
+        workspace.activate(DEFAULT_SLOT, &mut replay, &mut history);
+        assert_eq!(frames(&replay.clicks), [5]);
+    }
+
+    #[test]
+    fn slots_keep_their_own_history() {
+        let mut converter = Converter::new();
+        converter.set_tps(120.0);
+        converter.add_slot("other").unwrap();
+
+        converter.set_active_slot("other").unwrap();
+        assert!(!converter.can_undo());
+        converter.set_tps(30.0);
+
+        converter.set_active_slot(DEFAULT_SLOT).unwrap();
+        assert!(converter.undo());
+        assert!(!converter.can_undo());
+
+        converter.set_active_slot("other").unwrap();
+        assert!((converter.get_tps() - 30.0).abs() < f64::EPSILON);
+        assert!(converter.undo());
+    }
+
+    #[test]
+    fn with_slot_switches_back_when_loading_fails() {
+        let mut converter = Converter::new();
+        converter.add_slot("other").unwrap();
+
+        let result = converter
+            .with_slot("other", |converter| {
+                converter.load(b"not a replay".to_vec(), Format::CanonicalJson)
+            })
+            .unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(converter.active_slot(), DEFAULT_SLOT);
+    }
+
+    #[test]
+    fn load_slot_removes_the_slot_it_added_when_loading_fails() {
+        let mut converter = Converter::new();
+
+        assert!(converter
+            .load_slot("other", b"not a replay".to_vec(), Format::CanonicalJson)
+            .is_err());
+        assert_eq!(converter.slots(), [DEFAULT_SLOT]);
+
+        // Existing slots stay, with their replay
+        converter
+            .load_slot("other", REPLAY.as_bytes().to_vec(), Format::CanonicalJson)
+            .unwrap();
+        assert!(converter
+            .load_slot("other", b"not a replay".to_vec(), Format::CanonicalJson)
+            .is_err());
+        assert_eq!(converter.slots(), [DEFAULT_SLOT, "other"]);
+
+        converter.set_active_slot("other").unwrap();
+        assert_eq!(frames(&converter.replay().clicks), [3]);
+    }
+}
@@ -6,4 +6,5 @@ type Settings = {
 
     plainTextEditor: boolean;
     crossVersionConverting: boolean;
+    versionProfiles: boolean;
 };
\ No newline at end of file